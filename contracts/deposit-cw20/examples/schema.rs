@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use deposit_cw20::msg::{Cw20DepositResponse, Cw721DepositResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use deposit_cw20::msg::{Cw20DepositResponse, Cw721DepositResponse, ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg, StatementResponse, AsksResponse, FloorPriceResponse, PortfolioResponse, SalesResponse, LastSaleResponse, CollectionStatsResponse, CollectionTwapResponse, UserStatsResponse, LeaderboardResponse, TokenMarketResponse, SimulationResponse, OwnershipResponse, PauseStatusResponse, ConfigResponse, PendingChangesResponse, CollectionResponse, CollectionsResponse, ModeratorsResponse, BlockedResponse};
 use deposit_cw20::state::{Cw20Deposits, Cw721Deposits};
 
 fn main() {
@@ -15,8 +15,29 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(SudoMsg), &out_dir);
     export_schema(&schema_for!(Cw20Deposits), &out_dir);
     export_schema(&schema_for!(Cw721Deposits), &out_dir);
     export_schema(&schema_for!(Cw20DepositResponse), &out_dir);
     export_schema(&schema_for!(Cw721DepositResponse), &out_dir);
+    export_schema(&schema_for!(StatementResponse), &out_dir);
+    export_schema(&schema_for!(OwnershipResponse), &out_dir);
+    export_schema(&schema_for!(PauseStatusResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(PendingChangesResponse), &out_dir);
+    export_schema(&schema_for!(CollectionResponse), &out_dir);
+    export_schema(&schema_for!(CollectionsResponse), &out_dir);
+    export_schema(&schema_for!(ModeratorsResponse), &out_dir);
+    export_schema(&schema_for!(BlockedResponse), &out_dir);
+    export_schema(&schema_for!(AsksResponse), &out_dir);
+    export_schema(&schema_for!(FloorPriceResponse), &out_dir);
+    export_schema(&schema_for!(PortfolioResponse), &out_dir);
+    export_schema(&schema_for!(SalesResponse), &out_dir);
+    export_schema(&schema_for!(LastSaleResponse), &out_dir);
+    export_schema(&schema_for!(TokenMarketResponse), &out_dir);
+    export_schema(&schema_for!(SimulationResponse), &out_dir);
+    export_schema(&schema_for!(CollectionStatsResponse), &out_dir);
+    export_schema(&schema_for!(CollectionTwapResponse), &out_dir);
+    export_schema(&schema_for!(UserStatsResponse), &out_dir);
+    export_schema(&schema_for!(LeaderboardResponse), &out_dir);
 }
@@ -1,32 +1,277 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Addr, OverflowError, StdError, Timestamp, Uint128};
 use thiserror::Error;
 
+// Every message starts with the code of its variant, see `ContractError::code`.
+// Codes are part of the public interface: never reuse or renumber them.
 #[derive(Error, Debug)]
 pub enum ContractError {
-    #[error("{0}")]
+    #[error("E9000: {0}")]
     Std(#[from] StdError),
 
-    #[error("Custom Error val: {val:?}")]
-    CustomError { val: String },
+    #[error("E9001: {0}")]
+    Overflow(#[from] OverflowError),
 
-    #[error("Invalid Owner")]
-    InvalidOwner {},
+    #[error("E9002: Invalid Cw20HookMsg")]
+    InvalidCw20HookMsg {},
 
-    #[error("Invalid Coin")]
-    InvalidCoin {},
+    #[error("E9003: Invalid Cw721HookMsg")]
+    InvalidCw721HookMsg {},
 
-    #[error("Bid from this address already exits for this token_id")]
-    InvalidBid {},
+    #[error("E1000: Protocol fee of {protocol_fee_bps} basis points exceeds 10000")]
+    InvalidFee { protocol_fee_bps: u64 },
 
-    #[error("No bids from this sender for this token_id")]
-    NoBidsForTokenID {},
+    #[error("E1001: Royalty cap of {max_royalty_bps} basis points exceeds 10000")]
+    InvalidRoyaltyCap { max_royalty_bps: u64 },
 
-    #[error("User does not have coins from this cw20 to withdraw")]
-    NoCw20ToWithdraw {},
+    #[error("E1002: {asset} is not an allowed payment asset")]
+    PaymentAssetNotAllowed { asset: Addr },
 
-    #[error("Contract does not possess token_id from this cw721 to withdraw")]
-    NoCw721ToWithdraw {},
+    #[error("E1003: Royalty of {royalty_bps} basis points exceeds the cap of {max_royalty_bps}")]
+    RoyaltyTooHigh { royalty_bps: u64, max_royalty_bps: u64 },
 
-    #[error("Contract does not possess bids for this token")]
-    NoBidToWithdraw {},
+    #[error("E1004: {asset} is not an allowed payment asset for {cw721_contract}")]
+    CollectionAssetNotAllowed { cw721_contract: Addr, asset: Addr },
+
+    #[error("E1005: Fee discount of {fee_discount_bps} basis points exceeds 10000")]
+    InvalidFeeDiscount { fee_discount_bps: u64 },
+
+    #[error("E1006: Royalty of {royalty_bps} and protocol fee of {protocol_fee_bps} basis points exceed 10000 together")]
+    FeesTooHigh { royalty_bps: u64, protocol_fee_bps: u64 },
+
+    #[error("E2000: No ask for token {token_id} of {cw721_contract}")]
+    AskNotFound { cw721_contract: Addr, token_id: String },
+
+    #[error("E2001: Token {token_id} of {cw721_contract} is already deposited")]
+    AlreadyDeposited { cw721_contract: Addr, token_id: String },
+
+    #[error("E2002: Contract does not possess token {token_id} of {cw721_contract} for {owner}")]
+    NoCw721ToWithdraw { cw721_contract: Addr, token_id: String, owner: Addr },
+
+    #[error("E2003: Only {owner} can do this, not {sender}")]
+    InvalidOwner { owner: Addr, sender: Addr },
+
+    #[error("E2004: Collection {cw721_contract} is not registered")]
+    CollectionNotRegistered { cw721_contract: Addr },
+
+    #[error("E2005: Listing of token {token_id} of {cw721_contract} expired at {expires_at}")]
+    AskExpired { cw721_contract: Addr, token_id: String, expires_at: Timestamp },
+
+    #[error("E2006: No listing of {cw721_contract} fits the sweep limits")]
+    NothingToSweep { cw721_contract: Addr },
+
+    #[error("E2007: Collection {cw721_contract} was removed by the admin")]
+    CollectionRemoved { cw721_contract: Addr },
+
+    #[error("E3000: Ask is priced in {expected}, received {received}")]
+    InvalidCoin { expected: Addr, received: Addr },
+
+    #[error("E3001: Ask is {expected}, received {received}")]
+    InvalidAmount { expected: Uint128, received: Uint128 },
+
+    #[error("E3002: Price of {price} is below the collection minimum of {min_price}")]
+    PriceBelowMinimum { price: Uint128, min_price: Uint128 },
+
+    //E4000 (bid not below the ask) is retired, such bids now buy the token
+
+    #[error("E4001: {bidder} already has a bid on token {token_id} of {cw721_contract}")]
+    InvalidBid { cw721_contract: Addr, token_id: String, bidder: Addr },
+
+    #[error("E4002: Bid of {bid} must be higher than the current bid of {highest}")]
+    BidTooLow { bid: Uint128, highest: Uint128 },
+
+    #[error("E4003: No bid from {bidder} on token {token_id} of {cw721_contract}")]
+    NoBidsForTokenID { cw721_contract: Addr, token_id: String, bidder: Addr },
+
+    #[error("E4004: {bidder} has no bid to withdraw on token {token_id} of {cw721_contract}")]
+    NoBidToWithdraw { cw721_contract: Addr, token_id: String, bidder: Addr },
+
+    #[error("E4005: Bid of {bid} is below the minimum next bid of {min_bid}")]
+    BidIncrementTooLow { bid: Uint128, min_bid: Uint128 },
+
+    #[error("E4006: Token {token_id} of {cw721_contract} already has bids in {max} payment assets")]
+    TooManyBids { cw721_contract: Addr, token_id: String, max: u32 },
+
+    #[error("E5000: {owner} has no deposit of {contract} to withdraw")]
+    NoCw20ToWithdraw { contract: Addr, owner: Addr },
+
+    #[error("E5001: Deposit of {available} is less than the {requested} requested")]
+    InsufficientDeposit { available: Uint128, requested: Uint128 },
+
+    #[error("E6000: Cannot migrate a {found} contract, expected {expected}")]
+    ForeignContract { expected: String, found: String },
+
+    #[error("E6001: Cannot migrate from {stored} to the older {new}")]
+    Downgrade { stored: String, new: String },
+
+    #[error("E6002: Invalid contract version {version}")]
+    InvalidVersion { version: String },
+
+    #[error("E6003: Migrating from {version} needs a fee recipient or an admin")]
+    NoFeeRecipient { version: String },
+
+    #[error("E7000: {sender} is not the admin")]
+    NotAdmin { sender: Addr },
+
+    #[error("E7001: No admin handover is pending")]
+    NoPendingAdmin {},
+
+    #[error("E7002: {sender} is not the proposed admin")]
+    NotPendingAdmin { sender: Addr },
+
+    #[error("E7003: Admin proposal expired")]
+    AdminProposalExpired {},
+
+    #[error("E7004: No queued config change {id}")]
+    ConfigChangeNotFound { id: u64 },
+
+    #[error("E7005: Config change {id} can not be applied before {effective_at}")]
+    ConfigChangeNotReady { id: u64, effective_at: Timestamp },
+
+    #[error("E7006: {sender} is not a moderator")]
+    NotModerator { sender: Addr },
+
+    #[error("E7007: {sender} is not the minter of {cw721_contract}")]
+    NotMinter { sender: Addr, cw721_contract: Addr },
+
+    #[error("E7008: {sender} is not the creator of {cw721_contract}")]
+    NotCollectionCreator { sender: Addr, cw721_contract: Addr },
+
+    #[error("E8000: {switch} are paused, withdrawals remain available")]
+    Paused { switch: String },
+
+    #[error("E8001: Token {token_id} of {cw721_contract} is blocked: {reason}")]
+    TokenBlocked { cw721_contract: Addr, token_id: String, reason: String },
+
+    #[error("E8002: Collection {cw721_contract} is blocked: {reason}")]
+    CollectionBlocked { cw721_contract: Addr, reason: String },
+
+    #[error("E8003: Token {token_id} of {cw721_contract} is not blocked")]
+    NotBlocked { cw721_contract: Addr, token_id: String },
+
+    #[error("E8004: Collection {cw721_contract} is not blocked")]
+    CollectionNotBlocked { cw721_contract: Addr },
+}
+
+impl ContractError {
+    /// Stable code of the error, for clients that map failures to their own messages.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::Std(_) => "E9000",
+            ContractError::Overflow(_) => "E9001",
+            ContractError::InvalidCw20HookMsg {} => "E9002",
+            ContractError::InvalidCw721HookMsg {} => "E9003",
+            ContractError::InvalidFee { .. } => "E1000",
+            ContractError::InvalidRoyaltyCap { .. } => "E1001",
+            ContractError::PaymentAssetNotAllowed { .. } => "E1002",
+            ContractError::RoyaltyTooHigh { .. } => "E1003",
+            ContractError::CollectionAssetNotAllowed { .. } => "E1004",
+            ContractError::InvalidFeeDiscount { .. } => "E1005",
+            ContractError::FeesTooHigh { .. } => "E1006",
+            ContractError::AskNotFound { .. } => "E2000",
+            ContractError::AlreadyDeposited { .. } => "E2001",
+            ContractError::NoCw721ToWithdraw { .. } => "E2002",
+            ContractError::InvalidOwner { .. } => "E2003",
+            ContractError::CollectionNotRegistered { .. } => "E2004",
+            ContractError::AskExpired { .. } => "E2005",
+            ContractError::NothingToSweep { .. } => "E2006",
+            ContractError::CollectionRemoved { .. } => "E2007",
+            ContractError::InvalidCoin { .. } => "E3000",
+            ContractError::InvalidAmount { .. } => "E3001",
+            ContractError::PriceBelowMinimum { .. } => "E3002",
+            ContractError::InvalidBid { .. } => "E4001",
+            ContractError::BidTooLow { .. } => "E4002",
+            ContractError::NoBidsForTokenID { .. } => "E4003",
+            ContractError::NoBidToWithdraw { .. } => "E4004",
+            ContractError::BidIncrementTooLow { .. } => "E4005",
+            ContractError::TooManyBids { .. } => "E4006",
+            ContractError::NoCw20ToWithdraw { .. } => "E5000",
+            ContractError::InsufficientDeposit { .. } => "E5001",
+            ContractError::ForeignContract { .. } => "E6000",
+            ContractError::Downgrade { .. } => "E6001",
+            ContractError::InvalidVersion { .. } => "E6002",
+            ContractError::NoFeeRecipient { .. } => "E6003",
+            ContractError::NotAdmin { .. } => "E7000",
+            ContractError::NoPendingAdmin {} => "E7001",
+            ContractError::NotPendingAdmin { .. } => "E7002",
+            ContractError::AdminProposalExpired {} => "E7003",
+            ContractError::ConfigChangeNotFound { .. } => "E7004",
+            ContractError::ConfigChangeNotReady { .. } => "E7005",
+            ContractError::NotModerator { .. } => "E7006",
+            ContractError::NotMinter { .. } => "E7007",
+            ContractError::NotCollectionCreator { .. } => "E7008",
+            ContractError::Paused { .. } => "E8000",
+            ContractError::TokenBlocked { .. } => "E8001",
+            ContractError::CollectionBlocked { .. } => "E8002",
+            ContractError::NotBlocked { .. } => "E8003",
+            ContractError::CollectionNotBlocked { .. } => "E8004",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_start_with_their_code() {
+        let addr = Addr::unchecked("addr");
+        let amount = Uint128::zero();
+        let token_id = "1".to_string();
+        let errors = vec![
+            ContractError::Std(StdError::generic_err("err")),
+            ContractError::Overflow(OverflowError::new(cosmwasm_std::OverflowOperation::Sub, 0, 1)),
+            ContractError::InvalidCw20HookMsg {},
+            ContractError::InvalidCw721HookMsg {},
+            ContractError::InvalidFee { protocol_fee_bps: 0 },
+            ContractError::InvalidRoyaltyCap { max_royalty_bps: 0 },
+            ContractError::PaymentAssetNotAllowed { asset: addr.clone() },
+            ContractError::RoyaltyTooHigh { royalty_bps: 0, max_royalty_bps: 0 },
+            ContractError::CollectionAssetNotAllowed { cw721_contract: addr.clone(), asset: addr.clone() },
+            ContractError::InvalidFeeDiscount { fee_discount_bps: 0 },
+            ContractError::FeesTooHigh { royalty_bps: 0, protocol_fee_bps: 0 },
+            ContractError::AskNotFound { cw721_contract: addr.clone(), token_id: token_id.clone() },
+            ContractError::AlreadyDeposited { cw721_contract: addr.clone(), token_id: token_id.clone() },
+            ContractError::NoCw721ToWithdraw { cw721_contract: addr.clone(), token_id: token_id.clone(), owner: addr.clone() },
+            ContractError::InvalidOwner { owner: addr.clone(), sender: addr.clone() },
+            ContractError::CollectionNotRegistered { cw721_contract: addr.clone() },
+            ContractError::AskExpired { cw721_contract: addr.clone(), token_id: token_id.clone(), expires_at: Timestamp::from_seconds(0) },
+            ContractError::NothingToSweep { cw721_contract: addr.clone() },
+            ContractError::CollectionRemoved { cw721_contract: addr.clone() },
+            ContractError::InvalidCoin { expected: addr.clone(), received: addr.clone() },
+            ContractError::InvalidAmount { expected: amount, received: amount },
+            ContractError::PriceBelowMinimum { price: amount, min_price: amount },
+            ContractError::InvalidBid { cw721_contract: addr.clone(), token_id: token_id.clone(), bidder: addr.clone() },
+            ContractError::BidTooLow { bid: amount, highest: amount },
+            ContractError::NoBidsForTokenID { cw721_contract: addr.clone(), token_id: token_id.clone(), bidder: addr.clone() },
+            ContractError::NoBidToWithdraw { cw721_contract: addr.clone(), token_id: token_id.clone(), bidder: addr.clone() },
+            ContractError::BidIncrementTooLow { bid: amount, min_bid: amount },
+            ContractError::TooManyBids { cw721_contract: addr.clone(), token_id, max: 0 },
+            ContractError::NoCw20ToWithdraw { contract: addr.clone(), owner: addr.clone() },
+            ContractError::InsufficientDeposit { available: amount, requested: amount },
+            ContractError::ForeignContract { expected: String::new(), found: String::new() },
+            ContractError::Downgrade { stored: String::new(), new: String::new() },
+            ContractError::InvalidVersion { version: String::new() },
+            ContractError::NoFeeRecipient { version: String::new() },
+            ContractError::NotAdmin { sender: addr.clone() },
+            ContractError::NoPendingAdmin {},
+            ContractError::NotPendingAdmin { sender: addr.clone() },
+            ContractError::AdminProposalExpired {},
+            ContractError::ConfigChangeNotFound { id: 0 },
+            ContractError::ConfigChangeNotReady { id: 0, effective_at: Timestamp::from_seconds(0) },
+            ContractError::NotModerator { sender: addr.clone() },
+            ContractError::NotMinter { sender: addr.clone(), cw721_contract: addr.clone() },
+            ContractError::NotCollectionCreator { sender: addr.clone(), cw721_contract: addr.clone() },
+            ContractError::Paused { switch: String::new() },
+            ContractError::TokenBlocked { cw721_contract: addr.clone(), token_id: String::new(), reason: String::new() },
+            ContractError::CollectionBlocked { cw721_contract: addr.clone(), reason: String::new() },
+            ContractError::NotBlocked { cw721_contract: addr.clone(), token_id: String::new() },
+            ContractError::CollectionNotBlocked { cw721_contract: addr },
+        ];
+        let mut codes = vec![];
+        for err in errors {
+            assert!(err.to_string().starts_with(&format!("{}: ", err.code())), "{}", err);
+            assert!(!codes.contains(&err.code()), "duplicate code {}", err.code());
+            codes.push(err.code());
+        }
+    }
 }
@@ -0,0 +1,141 @@
+//! Events emitted for indexers. Chains prefix their type with `wasm-`, so `list`
+//! is indexed as `wasm-list`. Every event carries a `version` attribute that is
+//! bumped whenever an attribute is renamed, removed or changes meaning; new
+//! attributes may be appended without a bump.
+
+use cosmwasm_std::{Addr, Event, Uint128};
+
+use crate::msg::SaleBreakdown;
+use crate::state::{Bid, BlockedItem, Offer, Sale};
+
+pub const EVENT_VERSION: &str = "1";
+
+/// An NFT entered escrow with an ask.
+///
+/// Attributes: `version`, `cw721_contract`, `token_id`, `seller`, `payment_asset`, `price`,
+/// `expires_at` (optional).
+pub fn list_event(ask: &Offer) -> Event {
+    let event = Event::new("list")
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("cw721_contract", &ask.cw721_contract)
+        .add_attribute("token_id", &ask.token_id)
+        .add_attribute("seller", &ask.owner)
+        .add_attribute("payment_asset", &ask.cw20_contract)
+        .add_attribute("price", ask.amount);
+    match ask.expires_at {
+        Some(expires_at) => event.add_attribute("expires_at", expires_at.to_string()),
+        None => event
+    }
+}
+
+/// The owner took an NFT out of escrow, cancelling its ask. Delistings forced by
+/// governance append a `reason` attribute.
+///
+/// Attributes: `version`, `cw721_contract`, `token_id`, `seller`.
+pub fn delist_event(cw721_contract: &Addr, token_id: &str, seller: &Addr) -> Event {
+    Event::new("delist")
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("cw721_contract", cw721_contract)
+        .add_attribute("token_id", token_id)
+        .add_attribute("seller", seller)
+}
+
+/// An escrowed NFT changed hands. `kind` is `purchase` when the buyer paid the ask,
+/// `accept_bid` when the seller accepted the buyer's bid, `bid_match` when a bid at or above
+/// the ask bought the token at the ask and `listing_match` when a new listing filled the best
+/// standing bid at the bid's amount.
+///
+/// Attributes: `version`, `kind`, `sale_id`, `cw721_contract`, `token_id`, `seller`, `buyer`,
+/// `payment_asset`, `price`, `protocol_fee`, `royalty`, `seller_proceeds`.
+pub fn sale_event(kind: &str, sale: &Sale, breakdown: &SaleBreakdown) -> Event {
+    Event::new("sale")
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("kind", kind)
+        .add_attribute("sale_id", sale.id.to_string())
+        .add_attribute("cw721_contract", &sale.cw721_contract)
+        .add_attribute("token_id", &sale.token_id)
+        .add_attribute("seller", &sale.seller)
+        .add_attribute("buyer", &sale.buyer)
+        .add_attribute("payment_asset", &sale.payment_asset)
+        .add_attribute("price", sale.price)
+        .add_attribute("protocol_fee", breakdown.protocol_fee)
+        .add_attribute("royalty", breakdown.royalty)
+        .add_attribute("seller_proceeds", breakdown.seller_proceeds)
+}
+
+/// A moderator blocked a token, or a whole collection when `token_id` is absent.
+/// A blocked token's listing is returned with a `delist` event carrying the same `reason`;
+/// listings of a blocked collection are returned the same way by `DelistBlocked`.
+///
+/// Attributes: `version`, `cw721_contract`, `token_id` (optional), `reason`, `moderator`.
+pub fn block_event(blocked: &BlockedItem) -> Event {
+    moderation_attributes(Event::new("block"), &blocked.cw721_contract, blocked.token_id.as_deref(), &blocked.reason, &blocked.blocked_by)
+}
+
+/// A moderator lifted a block.
+///
+/// Attributes: same as `block`.
+pub fn unblock_event(cw721_contract: &Addr, token_id: Option<&str>, reason: &str, moderator: &Addr) -> Event {
+    moderation_attributes(Event::new("unblock"), cw721_contract, token_id, reason, moderator)
+}
+
+fn moderation_attributes(event: Event, cw721_contract: &Addr, token_id: Option<&str>, reason: &str, moderator: &Addr) -> Event {
+    let event = event
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("cw721_contract", cw721_contract);
+    let event = match token_id {
+        Some(token_id) => event.add_attribute("token_id", token_id),
+        None => event
+    };
+    event
+        .add_attribute("reason", reason)
+        .add_attribute("moderator", moderator)
+}
+
+/// Funds were locked in a bid.
+///
+/// Attributes: `version`, `cw721_contract`, `token_id`, `bidder`, `payment_asset`, `amount`.
+pub fn bid_event(bid: &Bid) -> Event {
+    bid_attributes(Event::new("bid"), bid)
+}
+
+/// A bid was withdrawn and its funds returned to the bidder. Bids refunded because a
+/// higher bid in the same payment asset replaced them append `reason` set to `outbid`.
+///
+/// Attributes: same as `bid`.
+pub fn bid_withdraw_event(bid: &Bid) -> Event {
+    bid_attributes(Event::new("bid-withdraw"), bid)
+}
+
+fn bid_attributes(event: Event, bid: &Bid) -> Event {
+    event
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("cw721_contract", &bid.cw721_contract)
+        .add_attribute("token_id", &bid.token_id)
+        .add_attribute("bidder", &bid.bidder)
+        .add_attribute("payment_asset", &bid.cw20_contract)
+        .add_attribute("amount", bid.amount)
+}
+
+/// Cw20 funds were added to an owner's deposit. `balance` is the deposit afterwards.
+///
+/// Attributes: `version`, `owner`, `payment_asset`, `amount`, `balance`.
+pub fn deposit_event(owner: &Addr, payment_asset: &Addr, amount: Uint128, balance: Uint128) -> Event {
+    cw20_attributes(Event::new("deposit"), owner, payment_asset, amount, balance)
+}
+
+/// Cw20 funds were taken out of an owner's deposit.
+///
+/// Attributes: same as `deposit`.
+pub fn withdraw_event(owner: &Addr, payment_asset: &Addr, amount: Uint128, balance: Uint128) -> Event {
+    cw20_attributes(Event::new("withdraw"), owner, payment_asset, amount, balance)
+}
+
+fn cw20_attributes(event: Event, owner: &Addr, payment_asset: &Addr, amount: Uint128, balance: Uint128) -> Event {
+    event
+        .add_attribute("version", EVENT_VERSION)
+        .add_attribute("owner", owner)
+        .add_attribute("payment_asset", payment_asset)
+        .add_attribute("amount", amount)
+        .add_attribute("balance", balance)
+}
@@ -1,24 +1,52 @@
 #[cfg(test)]
 mod tests {
     use crate::helpers::DepositContract;
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, Cw20HookMsg, Cw20DepositResponse, Cw721HookMsg, Cw721DepositResponse, BidsResponse};
-    use cosmwasm_std::{Addr, Coin, Empty, Uint128, to_binary};
-    use cw20::{Cw20Contract, Cw20Coin, MinterResponse, BalanceResponse};
+    use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg, Cw20HookMsg, Cw20DepositResponse, Cw721HookMsg, Cw721DepositResponse, BidsResponse, StatementResponse, AsksResponse, FloorPriceResponse, OrderBy, PortfolioResponse, PortfolioHasMore, SalesResponse, LastSaleResponse, CollectionStatsResponse, CollectionTwapResponse, UserStatsResponse, LeaderboardResponse, TokenMarketResponse, SimulationResponse, OwnershipResponse, PauseSwitch, PauseStatusResponse, ConfigResponse, PendingChangesResponse, CollectionResponse, ModeratorsResponse, BlockedResponse};
+    use crate::state::{CollectionRules, ConfigChange, LedgerAction, Pauses, TRADER_RANKED_VOLUME};
+    use crate::migrations::save_legacy_state;
+    use crate::stats::MAX_EXPIRED_SKIP;
+    use cosmwasm_std::{Addr, Binary, Coin, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128, to_binary};
+    use cw2::{query_contract_info, set_contract_version};
+    use cw_utils::Expiration;
+    use cw20::{Cw20Contract, Cw20Coin, BalanceResponse};
     use cw20_base::msg::ExecuteMsg as Cw20ExecuteMsg;
     use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
     use cw20_base::msg::QueryMsg as Cw20QueryMsg;
     use cw721::OwnerOfResponse;
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
 
     use cw20_example::{self};
     use nft::helpers::NftContract;
     use nft::{self};
+    use serde::{Deserialize, Serialize};
 
     pub fn contract_deposit_cw20() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
             crate::contract::execute,
             crate::contract::instantiate,
             crate::contract::query,
+        ).with_migrate(crate::contract::migrate).with_sudo(crate::contract::sudo);
+        Box::new(contract)
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct LegacyInstantiateMsg {
+        contract: String,
+        version: String
+    }
+
+    //stands in for a deployed older build: stores its cw2 info and the 0.1.0 state layout
+    fn legacy_instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: LegacyInstantiateMsg) -> StdResult<Response> {
+        set_contract_version(deps.storage, msg.contract, msg.version)?;
+        save_legacy_state(deps.storage)?;
+        Ok(Response::default())
+    }
+
+    pub fn contract_legacy_deposit_cw20() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            legacy_instantiate,
+            crate::contract::query,
         );
         Box::new(contract)
     }
@@ -43,7 +71,7 @@ mod tests {
 
     const USER: &str = "juno10c3slrqx3369mfsr9670au22zvq082jaej8ve4";
     const USER2: &str = "juno10c3slrqx3369mfsr9670au22zvq082jaejxx23";
-    const ADMIN: &str = "ADMIN";
+    const ADMIN: &str = "admin";
     const NATIVE_DENOM: &str = "denom";
 
     fn mock_app() -> App {
@@ -71,7 +99,11 @@ mod tests {
     }
 
     fn deposit_instantiate(app: &mut App, deposit_id: u64) -> DepositContract {
-        let msg = InstantiateMsg {};
+        deposit_instantiate_with_fee(app, deposit_id, 0)
+    }
+
+    fn deposit_instantiate_with_fee(app: &mut App, deposit_id: u64, protocol_fee_bps: u64) -> DepositContract {
+        let msg = InstantiateMsg { protocol_fee_bps, fee_recipient: None, admin: None };
         let deposit_contract_address = app
             .instantiate_contract(
                 deposit_id,
@@ -117,13 +149,19 @@ mod tests {
 
     fn get_cw20_deposits(app: &App, deposit_contract: &DepositContract) -> Cw20DepositResponse {
         app.wrap()
-            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER.to_string() })
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER.to_string(), start_after: None, limit: None })
             .unwrap()
     }
 
     fn get_bids(app: &App, deposit_contract: &DepositContract, cw721_contract: &NftContract, token_id: String) -> BidsResponse {
         app.wrap()
-            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Bids { cw721_contract: cw721_contract.addr().to_string(), token_id})
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Bids { cw721_contract: cw721_contract.addr().to_string(), token_id, start_after: None, limit: None })
+            .unwrap()
+    }
+
+    fn get_statement(app: &App, deposit_contract: &DepositContract, cw20_contract: &Cw20Contract, address: &str) -> StatementResponse {
+        app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Statement { address: address.to_string(), contract: cw20_contract.addr().to_string(), start_after: None, limit: None })
             .unwrap()
     }
 
@@ -135,7 +173,7 @@ mod tests {
 
     fn get_cw721_deposits(app: &App, deposit_contract: &DepositContract, nft_contract:&NftContract) -> Cw721DepositResponse {
         app.wrap()
-            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw721Deposits { address: USER.to_string(), contract: nft_contract.addr().to_string() })
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw721Deposits { address: USER.to_string(), contract: nft_contract.addr().to_string(), start_after: None, limit: None })
             .unwrap()
     }
 
@@ -145,18 +183,42 @@ mod tests {
             .unwrap()
     }
 
-    fn mint_nft(app: &mut App, cw721_contract: &NftContract, token_id:String, token_uri:Option<String>, to:String) -> () {
+    fn mint_nft(app: &mut App, cw721_contract: &NftContract, token_id:String, token_uri:Option<String>, to:String) {
         let mint_msg = nft::contract::MintMsg{token_id, owner:to, token_uri, extension:None };
         let msg = nft::contract::ExecuteMsg::Mint(mint_msg);
         let cosmos_msg = cw721_contract.call(msg).unwrap();
         app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
     }
 
-    fn deposit_nft(app: &mut App, deposit_contract:&DepositContract, cw721_contract:&NftContract, cw20_contract: &Cw20Contract, token_id:String, amount:u128) {
-        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "0".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount };
-        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "0".to_string(), msg: to_binary(&hook_msg).unwrap() };
+    fn send_cw20(app: &mut App, sender: &str, cw20_contract: &Cw20Contract, deposit_contract: &DepositContract, amount: u128, hook_msg: &Cw20HookMsg) -> AppResponse {
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(amount), msg: to_binary(hook_msg).unwrap() };
+        let cosmos_msg = cw20_contract.call(msg).unwrap();
+        app.execute(Addr::unchecked(sender), cosmos_msg).unwrap()
+    }
+
+    fn deposit_nft(app: &mut App, deposit_contract:&DepositContract, cw721_contract:&NftContract, cw20_contract: &Cw20Contract, token_id:String, amount:u128) -> AppResponse {
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: token_id.clone(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(amount) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id, msg: to_binary(&hook_msg).unwrap() };
         let cosmos_msg = cw721_contract.call(msg).unwrap();
-        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
+        app.execute(Addr::unchecked(USER), cosmos_msg).unwrap()
+    }
+
+    //compares every custom event of a response, attribute by attribute
+    fn assert_events(res: &AppResponse, deposit_contract: &DepositContract, expected: &[(&str, Vec<(&str, String)>)]) {
+        let actual: Vec<(String, Vec<(String, String)>)> = res.events
+            .iter()
+            .filter(|event| event.ty.starts_with("wasm-"))
+            .map(|event| (event.ty.clone(), event.attributes.iter().map(|attr| (attr.key.clone(), attr.value.clone())).collect()))
+            .collect();
+        let expected: Vec<(String, Vec<(String, String)>)> = expected
+            .iter()
+            .map(|(ty, attributes)| {
+                let mut all = vec![("_contract_addr".to_string(), deposit_contract.addr().to_string())];
+                all.extend(attributes.iter().map(|(key, value)| (key.to_string(), value.clone())));
+                (format!("wasm-{}", ty), all)
+            })
+            .collect();
+        assert_eq!(actual, expected);
     }
 
     #[test]
@@ -168,7 +230,7 @@ mod tests {
         let balance = get_balance(&app, &cw20_contract, USER.to_string());
         println!("Intial Balance {:?}", balance);
 
-        let hook_msg = Cw20HookMsg::Deposit { owner: USER.to_string(), amount: 500 };
+        let hook_msg = Cw20HookMsg::Deposit { owner: USER.to_string() };
 
         let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(500u64), msg: to_binary(&hook_msg).unwrap() };
         let cosmos_msg = cw20_contract.call(msg).unwrap();
@@ -239,5 +301,1441 @@ mod tests {
         println!("Bids {:?}", bids);
     }
 
+    #[test]
+    fn statement_records_deposits_bids_and_refunds() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 500, &Cw20HookMsg::Deposit { owner: USER.to_string() });
+
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 500);
+
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 200, &place_bid);
+        // outbidding returns the first bid to its owner
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 300, &place_bid);
+
+        let msg = ExecuteMsg::WithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::from(100u64) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+
+        let balance = get_balance(&app, &cw20_contract, USER.to_string());
+        assert_eq!(balance.balance, Uint128::from(8600u64));
+
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER);
+        let actions: Vec<(LedgerAction, u128)> = statement.entries.iter().map(|e| (e.action.clone(), e.amount.u128())).collect();
+        assert_eq!(actions, vec![
+            (LedgerAction::Deposit, 500),
+            (LedgerAction::BidLock, 200),
+            (LedgerAction::Refund, 200),
+            (LedgerAction::Withdrawal, 100),
+        ]);
+        assert_eq!(statement.entries[1].token_id, Some("0".to_string()));
+        assert_eq!(statement.entries[0].block_height, app.block_info().height);
+
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER2);
+        assert_eq!(statement.entries.len(), 1);
+        assert_eq!(statement.entries[0].action, LedgerAction::BidLock);
+
+        // paging continues after the last seen entry id
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER);
+        let page: StatementResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Statement { address: USER.to_string(), contract: cw20_contract.addr().to_string(), start_after: Some(statement.entries[1].id), limit: Some(1) })
+            .unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].action, LedgerAction::Refund);
+    }
+
+    #[test]
+    fn outbid_bids_are_refunded_per_payment_asset() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 200, &place_bid);
+        let res = send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 300, &place_bid);
+        assert!(res.events.iter().any(|event| event.ty == "wasm-bid-withdraw" && event.attributes.iter().any(|attr| attr.key == "reason" && attr.value == "outbid")));
+
+        let bids = get_bids(&app, &deposit_contract, &cw721_contract, "0".to_string());
+        assert_eq!(bids.bids.iter().map(|bid| bid.bidder.as_str()).collect::<Vec<_>>(), vec![USER2]);
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::from(9000u64));
+
+        // a bidder holds at most one bid per token
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(400u64), msg: to_binary(&place_bid).unwrap() };
+        let err = app.execute(Addr::unchecked(USER2), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E4001: {} already has a bid on token 0 of {}", USER2, cw721_contract.addr()));
+
+        // new bids must beat the highest standing bid
+        let msg = Cw20ExecuteMsg::Transfer { recipient: ADMIN.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(250u64), msg: to_binary(&place_bid).unwrap() };
+        app.execute(Addr::unchecked(ADMIN), cw20_contract.call(msg).unwrap()).unwrap_err();
+
+        // bids in other payment assets are not compared, but a token only holds so many
+        for i in 0..9 {
+            let other = cw_20_instantiate(&mut app, cw20_id);
+            let bidder = format!("bidder{}", i);
+            let msg = Cw20ExecuteMsg::Transfer { recipient: bidder.clone(), amount: Uint128::from(100u64) };
+            app.execute(Addr::unchecked(USER), other.call(msg).unwrap()).unwrap();
+            send_cw20(&mut app, &bidder, &other, &deposit_contract, 10, &place_bid);
+        }
+        assert_eq!(get_bids(&app, &deposit_contract, &cw721_contract, "0".to_string()).bids.len(), 10);
+        let other = cw_20_instantiate(&mut app, cw20_id);
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(10u64), msg: to_binary(&place_bid).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), other.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E4006: Token 0 of {} already has bids in 10 payment assets", cw721_contract.addr()));
+
+        // deposits for an owner that is not a valid address are rejected
+        let hook_msg = Cw20HookMsg::Deposit { owner: "NOT_AN_ADDRESS".to_string() };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(10u64), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap_err();
+    }
+
+    #[test]
+    fn list_queries_are_paginated() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        for token_id in ["0", "1", "2"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), 500);
+        }
+
+        let page: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllAsks { start_after: None, limit: Some(2) })
+            .unwrap();
+        let token_ids: Vec<&str> = page.asks.iter().map(|ask| ask.token_id.as_str()).collect();
+        assert_eq!(token_ids, vec!["0", "1"]);
+
+        let last = page.asks.last().unwrap();
+        let page: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AllAsks { start_after: Some((last.cw721_contract.to_string(), last.token_id.clone())), limit: Some(2) })
+            .unwrap();
+        let token_ids: Vec<&str> = page.asks.iter().map(|ask| ask.token_id.as_str()).collect();
+        assert_eq!(token_ids, vec!["2"]);
+
+        let page: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksByCollection { cw721_contract: cw721_contract.addr().to_string(), start_after: Some("0".to_string()), limit: None })
+            .unwrap();
+        let token_ids: Vec<&str> = page.asks.iter().map(|ask| ask.token_id.as_str()).collect();
+        assert_eq!(token_ids, vec!["1", "2"]);
+
+        let page: Cw721DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw721Deposits { address: USER.to_string(), contract: cw721_contract.addr().to_string(), start_after: Some("1".to_string()), limit: Some(5) })
+            .unwrap();
+        assert_eq!(page.deposits.len(), 1);
+        assert_eq!(page.deposits[0].0, "2");
+    }
+
+    #[test]
+    fn asks_sorted_by_price_and_floor() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        for (token_id, amount) in [("0", 300), ("1", 100), ("2", 200)] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), amount);
+        }
+
+        let asks_by_price = |start_after: Option<(Uint128, String)>, order: Option<OrderBy>| -> Vec<String> {
+            let res: AsksResponse = app.wrap()
+                .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksByPrice { cw721_contract: cw721_contract.addr().to_string(), start_after, limit: None, order })
+                .unwrap();
+            res.asks.into_iter().map(|ask| ask.token_id).collect()
+        };
+        assert_eq!(asks_by_price(None, None), vec!["1", "2", "0"]);
+        assert_eq!(asks_by_price(None, Some(OrderBy::Descending)), vec!["0", "2", "1"]);
+        assert_eq!(asks_by_price(Some((Uint128::from(200u64), "2".to_string())), Some(OrderBy::Ascending)), vec!["0"]);
+        assert_eq!(asks_by_price(Some((Uint128::from(200u64), "2".to_string())), Some(OrderBy::Descending)), vec!["1"]);
+
+        let floor: FloorPriceResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::FloorPrice { cw721_contract: cw721_contract.addr().to_string(), payment_asset: cw20_contract.addr().to_string() })
+            .unwrap();
+        let ask = floor.ask.unwrap();
+        assert_eq!(ask.token_id, "1");
+        assert_eq!(ask.amount, Uint128::from(100u64));
+
+        let floor: FloorPriceResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::FloorPrice { cw721_contract: cw721_contract.addr().to_string(), payment_asset: deposit_contract.addr().to_string() })
+            .unwrap();
+        assert_eq!(floor.ask, None);
+    }
+
+    #[test]
+    fn asks_by_seller_and_bids_by_bidder() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let first = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let second = cw721_instantiate(&mut app, cw721_id, "OTHER".to_string(), "OTHER".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        for cw721_contract in [&first, &second] {
+            mint_nft(&mut app, cw721_contract, "0".to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, cw721_contract, &cw20_contract, "0".to_string(), 500);
+            let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+            send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid);
+        }
+
+        let res: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksBySeller { seller: USER.to_string(), start_after: None, limit: Some(1) })
+            .unwrap();
+        assert_eq!(res.asks.len(), 1);
+        let after = (res.asks[0].cw721_contract.to_string(), res.asks[0].token_id.clone());
+        let res: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksBySeller { seller: USER.to_string(), start_after: Some(after.clone()), limit: None })
+            .unwrap();
+        assert_eq!(res.asks.len(), 1);
+        assert_ne!(res.asks[0].cw721_contract.to_string(), after.0);
+
+        let res: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksBySeller { seller: USER2.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert!(res.asks.is_empty());
+
+        let res: BidsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::BidsByBidder { bidder: USER2.to_string(), start_after: None, limit: None })
+            .unwrap();
+        let collections: Vec<String> = res.bids.iter().map(|bid| bid.cw721_contract.to_string()).collect();
+        assert_eq!(collections.len(), 2);
+        assert!(collections.contains(&first.addr().to_string()));
+        assert!(collections.contains(&second.addr().to_string()));
+
+        let res: BidsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::BidsByBidder { bidder: USER2.to_string(), start_after: Some((collections[0].clone(), "0".to_string())), limit: None })
+            .unwrap();
+        assert_eq!(res.bids.len(), 1);
+        assert_eq!(res.bids[0].cw721_contract.to_string(), collections[1]);
+    }
+
+    #[test]
+    fn portfolio_spans_collections() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let first = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let second = cw721_instantiate(&mut app, cw721_id, "OTHER".to_string(), "OTHER".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        mint_nft(&mut app, &first, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &first, &cw20_contract, "0".to_string(), 500);
+        mint_nft(&mut app, &second, "7".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &second, &cw20_contract, "7".to_string(), 900);
+        mint_nft(&mut app, &second, "8".to_string(), None, USER2.to_string());
+
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 250, &Cw20HookMsg::Deposit { owner: USER.to_string() });
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: second.addr().to_string(), token_id: "8".to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 120, &place_bid);
+
+        let portfolio: PortfolioResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Portfolio { address: USER.to_string() })
+            .unwrap();
+        let mut escrowed: Vec<(String, String)> = portfolio.escrowed.iter().map(|d| (d.contract.to_string(), d.token_id.clone())).collect();
+        escrowed.sort();
+        let mut expected = vec![(first.addr().to_string(), "0".to_string()), (second.addr().to_string(), "7".to_string())];
+        expected.sort();
+        assert_eq!(escrowed, expected);
+        assert_eq!(portfolio.asks.len(), 2);
+        assert_eq!(portfolio.deposits.len(), 1);
+        assert_eq!(portfolio.deposits[0].amount, Uint128::from(250u64));
+        assert_eq!(portfolio.bids.len(), 1);
+        assert_eq!(portfolio.bids[0].token_id, "8");
+        assert_eq!(portfolio.bids[0].amount, Uint128::from(120u64));
+        assert_eq!(portfolio.has_more, PortfolioHasMore::default());
+
+        let portfolio: PortfolioResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Portfolio { address: USER2.to_string() })
+            .unwrap();
+        assert_eq!(portfolio, PortfolioResponse { escrowed: vec![], asks: vec![], deposits: vec![], bids: vec![], has_more: PortfolioHasMore::default() });
+
+        //lists longer than a page are flagged, expired asks are left out
+        let execute = |app: &mut App, msg: ExecuteMsg| app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        let nft = first.addr().to_string();
+        execute(&mut app, ExecuteMsg::RegisterCollection { cw721_contract: nft.clone(), name: "NFT".to_string(), royalty_bps: 0, royalty_recipient: None, allowed_assets: None });
+        let rules = CollectionRules { max_listing_duration: Some(60), ..CollectionRules::default() };
+        execute(&mut app, ExecuteMsg::SetCollectionRules { cw721_contract: nft, rules });
+        for token_id in 1..=30 {
+            mint_nft(&mut app, &first, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &first, &cw20_contract, token_id.to_string(), 500);
+        }
+        app.update_block(|block| block.time = block.time.plus_seconds(60));
+        let portfolio: PortfolioResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Portfolio { address: USER.to_string() })
+            .unwrap();
+        assert_eq!(portfolio.escrowed.len(), 30);
+        assert_eq!(portfolio.asks.iter().map(|ask| ask.token_id.as_str()).collect::<Vec<_>>(), vec!["0"]);
+        assert_eq!(portfolio.has_more, PortfolioHasMore { escrowed: true, asks: true, deposits: false, bids: false });
+    }
+
+    #[test]
+    fn purchase_pays_seller_and_records_sale() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        // 2.5% protocol fee paid to the instantiating admin
+        let deposit_contract = deposit_instantiate_with_fee(&mut app, deposit_id, 250);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(5000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 1000);
+
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 1000, &purchase);
+
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER2);
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::from(5975u64));
+        assert_eq!(get_balance(&app, &cw20_contract, ADMIN.to_string()).balance, Uint128::from(25u64));
+        assert_eq!(get_balance(&app, &cw20_contract, deposit_contract.addr().to_string()).balance, Uint128::zero());
+
+        // sold again by the new owner
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER2.to_string(), token_id: "0".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(2000u64) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "0".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(USER2), cw721_contract.call(msg).unwrap()).unwrap();
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 2000, &purchase);
+
+        let res: SalesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::SalesByToken { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(res.sales.len(), 2);
+        let latest = &res.sales[0];
+        assert_eq!(latest.seller, Addr::unchecked(USER2));
+        assert_eq!(latest.buyer, Addr::unchecked(USER));
+        assert_eq!(latest.price, Uint128::from(2000u64));
+        assert_eq!(latest.protocol_fee, Uint128::from(50u64));
+        assert_eq!(latest.payment_asset, cw20_contract.addr());
+        assert_eq!(latest.block_time, app.block_info().time);
+        assert_eq!(res.sales[1].seller, Addr::unchecked(USER));
+
+        let res: SalesResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::SalesByCollection { cw721_contract: cw721_contract.addr().to_string(), start_after: Some(latest.id), limit: None })
+            .unwrap();
+        assert_eq!(res.sales.len(), 1);
+        assert_eq!(res.sales[0].price, Uint128::from(1000u64));
+
+        let res: LastSaleResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::LastSale { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() })
+            .unwrap();
+        assert_eq!(res.sale.unwrap().id, latest.id);
+
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER2);
+        let actions: Vec<LedgerAction> = statement.entries.into_iter().map(|e| e.action).collect();
+        assert_eq!(actions, vec![LedgerAction::Purchase, LedgerAction::Proceeds]);
+    }
+
+    #[test]
+    fn collection_stats_follow_listings_and_sales() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(5000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        for (token_id, amount) in [("0", 300), ("1", 100), ("2", 200)] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), amount);
+        }
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 150, &place_bid);
+
+        let get_stats = |app: &App| -> CollectionStatsResponse {
+            app.wrap()
+                .query_wasm_smart(deposit_contract.addr(), &QueryMsg::CollectionStats { cw721_contract: cw721_contract.addr().to_string() })
+                .unwrap()
+        };
+        let stats = get_stats(&app);
+        assert_eq!((stats.listings, stats.sales, stats.escrow_holders), (3, 0, 1));
+        assert_eq!(stats.assets.len(), 1);
+        assert_eq!(stats.assets[0].floor_price, Some(Uint128::from(100u64)));
+        assert_eq!(stats.assets[0].best_offer, Some(Uint128::from(150u64)));
+
+        let purchase = |token_id: &str| Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: token_id.to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &purchase("1"));
+        app.update_block(|block| block.time = block.time.plus_seconds(2 * 24 * 60 * 60));
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 200, &purchase("2"));
+
+        let stats = get_stats(&app);
+        assert_eq!((stats.listings, stats.sales, stats.escrow_holders), (1, 2, 1));
+        let asset = &stats.assets[0];
+        assert_eq!(asset.floor_price, Some(Uint128::from(300u64)));
+        assert_eq!(asset.volume, Uint128::from(300u64));
+        assert_eq!(asset.volume_24h, Uint128::from(200u64));
+        assert_eq!(asset.volume_7d, Uint128::from(300u64));
+
+        let msg = ExecuteMsg::WithdrawNft { contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(7 * 24 * 60 * 60));
+
+        let stats = get_stats(&app);
+        assert_eq!((stats.listings, stats.sales, stats.escrow_holders), (0, 2, 0));
+        let asset = &stats.assets[0];
+        assert_eq!(asset.floor_price, None);
+        assert_eq!(asset.volume, Uint128::from(300u64));
+        assert_eq!(asset.volume_24h, Uint128::zero());
+        assert_eq!(asset.volume_7d, Uint128::zero());
+    }
+
+    #[test]
+    fn collection_twap_weights_prices_by_time() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(5000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        let get_twap = |app: &App, window: u64| -> StdResult<CollectionTwapResponse> {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::CollectionTwap { cw721_contract: cw721_contract.addr().to_string(), window })
+        };
+        assert!(get_twap(&app, 3600).unwrap().twaps.is_empty());
+        assert!(get_twap(&app, 0).is_err());
+
+        for (token_id, amount) in [("0", 100), ("1", 300)] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), amount);
+            let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: token_id.to_string() };
+            send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, amount, &purchase);
+            app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        }
+
+        let twap = get_twap(&app, 3600).unwrap();
+        assert_eq!(twap.twaps.len(), 1);
+        assert_eq!(twap.twaps[0].payment_asset, cw20_contract.addr());
+        assert_eq!((twap.twaps[0].twap, twap.twaps[0].observed), (Uint128::from(300u64), 3600));
+
+        let twap = get_twap(&app, 5400).unwrap();
+        assert_eq!((twap.twaps[0].twap, twap.twaps[0].observed), (Uint128::from(233u64), 5400));
+
+        //history shorter than the window averages since the first sale
+        let twap = get_twap(&app, 24 * 3600).unwrap();
+        assert_eq!((twap.twaps[0].twap, twap.twaps[0].observed), (Uint128::from(200u64), 7200));
+    }
+
+    #[test]
+    fn user_stats_and_leaderboard() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate_with_fee(&mut app, deposit_id, 1000);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        for buyer in [USER2, ADMIN] {
+            let msg = Cw20ExecuteMsg::Transfer { recipient: buyer.to_string(), amount: Uint128::from(1000u64) };
+            app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        }
+
+        let sell = |app: &mut App, token_id: &str, amount: u128, buyer: &str| {
+            mint_nft(app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), amount);
+            let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: token_id.to_string() };
+            send_cw20(app, buyer, &cw20_contract, &deposit_contract, amount, &purchase);
+        };
+        sell(&mut app, "0", 100, USER2);
+        app.update_block(|block| block.time = block.time.plus_seconds(2 * 24 * 60 * 60));
+        sell(&mut app, "1", 300, USER2);
+        sell(&mut app, "2", 200, ADMIN);
+
+        let stats: UserStatsResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::UserStats { address: USER.to_string() }).unwrap();
+        assert_eq!(stats.assets.len(), 1);
+        let seller = &stats.assets[0];
+        assert_eq!(seller.payment_asset, cw20_contract.addr());
+        assert_eq!((seller.volume_bought, seller.volume_sold, seller.trades), (Uint128::zero(), Uint128::from(600u64), 3));
+        assert_eq!((seller.fees_paid, seller.royalties_paid), (Uint128::from(60u64), Uint128::zero()));
+
+        let stats: UserStatsResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::UserStats { address: USER2.to_string() }).unwrap();
+        let buyer = &stats.assets[0];
+        assert_eq!((buyer.volume_bought, buyer.volume_sold, buyer.trades), (Uint128::from(400u64), Uint128::zero(), 2));
+        assert_eq!(buyer.fees_paid, Uint128::zero());
+
+        let get_leaderboard = |app: &App, days: u64, limit: Option<u32>| -> StdResult<LeaderboardResponse> {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Leaderboard { payment_asset: cw20_contract.addr().to_string(), days, limit })
+        };
+        let ranked = |board: LeaderboardResponse| -> Vec<(String, u128)> {
+            board.traders.into_iter().map(|trader| (trader.address.to_string(), trader.volume.u128())).collect()
+        };
+        assert_eq!(ranked(get_leaderboard(&app, 1, None).unwrap()), vec![(USER.to_string(), 500), (USER2.to_string(), 300), (ADMIN.to_string(), 200)]);
+        assert_eq!(ranked(get_leaderboard(&app, 7, Some(2)).unwrap()), vec![(USER.to_string(), 600), (USER2.to_string(), 400)]);
+        assert!(get_leaderboard(&app, 0, None).is_err());
+
+        //a month later only the new trade counts and idle traders have left the ranking
+        app.update_block(|block| block.time = block.time.plus_seconds(30 * 24 * 60 * 60));
+        sell(&mut app, "3", 50, ADMIN);
+        assert_eq!(ranked(get_leaderboard(&app, 30, None).unwrap()), vec![(ADMIN.to_string(), 50), (USER.to_string(), 50)]);
+        let ranked_volume = |trader: &str| app.wrap()
+            .query_wasm_raw(deposit_contract.addr(), TRADER_RANKED_VOLUME.key((&cw20_contract.addr(), &Addr::unchecked(trader))).to_vec())
+            .unwrap();
+        assert!(ranked_volume(USER2).is_none());
+        assert!(ranked_volume(ADMIN).is_some());
+    }
+
+    #[test]
+    fn token_market_combines_ask_bids_and_sales() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        for bidder in [USER2, ADMIN] {
+            let msg = Cw20ExecuteMsg::Transfer { recipient: bidder.to_string(), amount: Uint128::from(1000u64) };
+            app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        }
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 500);
+
+        //bids in different payment assets stand side by side
+        let other_cw20 = cw_20_instantiate(&mut app, cw20_id);
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), other_cw20.call(msg).unwrap()).unwrap();
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &other_cw20, &deposit_contract, 100, &place_bid);
+        send_cw20(&mut app, ADMIN, &cw20_contract, &deposit_contract, 200, &place_bid);
+
+        let get_market = |app: &App| -> TokenMarketResponse {
+            app.wrap()
+                .query_wasm_smart(deposit_contract.addr(), &QueryMsg::TokenMarket { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() })
+                .unwrap()
+        };
+        let market = get_market(&app);
+        assert_eq!(market.owner, Addr::unchecked(USER));
+        assert!(market.escrowed);
+        assert_eq!(market.ask.unwrap().amount, Uint128::from(500u64));
+        let ranked: Vec<_> = market.bids.iter().map(|bid| (bid.bidder.as_str(), bid.amount.u128())).collect();
+        assert_eq!(ranked, vec![(ADMIN, 200), (USER2, 100)]);
+        assert_eq!(market.last_sale, None);
+
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 500, &purchase);
+
+        let market = get_market(&app);
+        assert_eq!(market.owner, Addr::unchecked(USER2));
+        assert!(!market.escrowed);
+        assert_eq!(market.ask, None);
+        let sale = market.last_sale.unwrap();
+        assert_eq!((sale.buyer, sale.price), (Addr::unchecked(USER2), Uint128::from(500u64)));
+    }
+
+    #[test]
+    fn simulations_match_purchase_and_accepted_bid() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate_with_fee(&mut app, deposit_id, 1000);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 500);
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 200, &place_bid);
+
+        let simulate_purchase = |app: &App, payment_asset: &Addr| -> SimulationResponse {
+            let msg = QueryMsg::SimulatePurchase { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string(), payment_asset: payment_asset.to_string() };
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &msg).unwrap()
+        };
+        let simulate_accept_bid = |app: &App, bidder: &str| -> SimulationResponse {
+            let msg = QueryMsg::SimulateAcceptBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string(), bidder: bidder.to_string() };
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &msg).unwrap()
+        };
+
+        let simulated = simulate_purchase(&app, &cw20_contract.addr()).breakdown.unwrap();
+        assert_eq!(simulated.payment_asset, cw20_contract.addr());
+        assert_eq!((simulated.price, simulated.protocol_fee, simulated.royalty, simulated.seller_proceeds),
+            (Uint128::from(500u64), Uint128::from(50u64), Uint128::zero(), Uint128::from(450u64)));
+        let simulated = simulate_purchase(&app, &cw721_contract.addr());
+        assert_eq!(simulated.breakdown, None);
+        assert!(simulated.error.unwrap().starts_with("E3000: "));
+        assert!(simulate_accept_bid(&app, ADMIN).error.is_some());
+
+        let simulated = simulate_accept_bid(&app, USER2).breakdown.unwrap();
+        assert_eq!((simulated.price, simulated.protocol_fee, simulated.seller_proceeds), (Uint128::from(200u64), Uint128::from(20u64), Uint128::from(180u64)));
+
+        //only the seller can accept
+        let msg = ExecuteMsg::AcceptBid { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string(), bidder: USER2.to_string() };
+        app.execute(Addr::unchecked(USER2), deposit_contract.call(msg.clone()).unwrap()).unwrap_err();
+
+        let seller_before = get_balance(&app, &cw20_contract, USER.to_string()).balance;
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER2.to_string());
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, seller_before + simulated.seller_proceeds);
+        assert_eq!(get_balance(&app, &cw20_contract, ADMIN.to_string()).balance, simulated.protocol_fee);
+        assert!(get_bids(&app, &deposit_contract, &cw721_contract, "0".to_string()).bids.is_empty());
+        assert!(simulate_purchase(&app, &cw20_contract.addr()).error.is_some());
+    }
+
+    #[test]
+    fn errors_carry_codes_and_context() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 500);
+
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(400u64), msg: to_binary(&purchase).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E3001: Ask is 500, received 400");
+
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: cw721_contract.addr().to_string(), token_id: "1".to_string() };
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(400u64), msg: to_binary(&purchase).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E2000: No ask for token 1 of {}", cw721_contract.addr()));
+
+        let hook_msg = Cw20HookMsg::Deposit { owner: USER.to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 10, &hook_msg);
+        let msg = ExecuteMsg::WithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::from(11u64) };
+        let err = app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E5001: Deposit of 10 is less than the 11 requested");
+
+        let msg = ExecuteMsg::WithdrawBid { contract: cw721_contract.addr().to_string(), token_id: "0".to_string() };
+        let err = app.execute(Addr::unchecked(USER2), deposit_contract.call(msg).unwrap()).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E4004: "));
+    }
+
+    #[test]
+    fn deposits_credit_the_sent_amount() {
+        let (mut app, deposit_id, cw20_id, _cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 5000, &Cw20HookMsg::Deposit { owner: USER.to_string() });
+
+        //an amount claimed in the hook message is not what gets credited
+        let hook_msg = format!(r#"{{"deposit":{{"owner":"{}","amount":"5000"}}}}"#, USER2);
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(1u64), msg: Binary::from(hook_msg.as_bytes()) };
+        app.execute(Addr::unchecked(USER2), cw20_contract.call(msg).unwrap()).unwrap();
+        let deposits: Cw20DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: USER2.to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(deposits.deposits[0].1.amount, Uint128::from(1u64));
+
+        let msg = ExecuteMsg::WithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::from(5001u64) };
+        let err = app.execute(Addr::unchecked(USER2), deposit_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E5001: Deposit of 1 is less than the 5001 requested");
+        assert_eq!(get_balance(&app, &cw20_contract, deposit_contract.addr().to_string()).balance, Uint128::from(5001u64));
+    }
+
+    #[test]
+    fn execute_paths_emit_events() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate_with_fee(&mut app, deposit_id, 1000);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let cw20 = cw20_contract.addr().to_string();
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+
+        let hook_msg = Cw20HookMsg::Deposit { owner: USER.to_string() };
+        let res = send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 100, &hook_msg);
+        assert_events(&res, &deposit_contract, &[("deposit", vec![
+            ("version", "1".to_string()), ("owner", USER.to_string()), ("payment_asset", cw20.clone()), ("amount", "100".to_string()), ("balance", "100".to_string())
+        ])]);
+
+        let msg = ExecuteMsg::WithdrawCw20 { contract: cw20.clone(), amount: Uint128::from(40u64) };
+        let res = app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        assert_events(&res, &deposit_contract, &[("withdraw", vec![
+            ("version", "1".to_string()), ("owner", USER.to_string()), ("payment_asset", cw20.clone()), ("amount", "40".to_string()), ("balance", "60".to_string())
+        ])]);
+
+        for token_id in ["0", "1"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            let res = deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), 500);
+            assert_events(&res, &deposit_contract, &[("list", vec![
+                ("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", token_id.to_string()), ("seller", USER.to_string()),
+                ("payment_asset", cw20.clone()), ("price", "500".to_string())
+            ])]);
+        }
+
+        let bid_attributes = |token_id: &str, amount: &str| vec![
+            ("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", token_id.to_string()), ("bidder", USER2.to_string()),
+            ("payment_asset", cw20.clone()), ("amount", amount.to_string())
+        ];
+        let place_bid = |token_id: &str| Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: token_id.to_string() };
+        let res = send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 200, &place_bid("0"));
+        assert_events(&res, &deposit_contract, &[("bid", bid_attributes("0", "200"))]);
+
+        let msg = ExecuteMsg::WithdrawBid { contract: nft.clone(), token_id: "0".to_string() };
+        let res = app.execute(Addr::unchecked(USER2), deposit_contract.call(msg).unwrap()).unwrap();
+        assert_events(&res, &deposit_contract, &[("bid-withdraw", bid_attributes("0", "200"))]);
+
+        let sale_attributes = |kind: &str, sale_id: &str, token_id: &str, price: &str, fee: &str, proceeds: &str| vec![
+            ("version", "1".to_string()), ("kind", kind.to_string()), ("sale_id", sale_id.to_string()), ("cw721_contract", nft.clone()),
+            ("token_id", token_id.to_string()), ("seller", USER.to_string()), ("buyer", USER2.to_string()), ("payment_asset", cw20.clone()),
+            ("price", price.to_string()), ("protocol_fee", fee.to_string()), ("royalty", "0".to_string()), ("seller_proceeds", proceeds.to_string())
+        ];
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: nft.clone(), token_id: "0".to_string() };
+        let res = send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 500, &purchase);
+        assert_events(&res, &deposit_contract, &[("sale", sale_attributes("purchase", "1", "0", "500", "50", "450"))]);
+
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 300, &place_bid("1"));
+        let msg = ExecuteMsg::AcceptBid { cw721_contract: nft.clone(), token_id: "1".to_string(), bidder: USER2.to_string() };
+        let res = app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        assert_events(&res, &deposit_contract, &[("sale", sale_attributes("accept_bid", "2", "1", "300", "30", "270"))]);
+
+        mint_nft(&mut app, &cw721_contract, "2".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "2".to_string(), 500);
+        let msg = ExecuteMsg::WithdrawNft { contract: nft.clone(), token_id: "2".to_string() };
+        let res = app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        assert_events(&res, &deposit_contract, &[("delist", vec![
+            ("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "2".to_string()), ("seller", USER.to_string())
+        ])]);
+    }
+
+    fn legacy_instantiate_as(app: &mut App, contract: &str, version: &str) -> (Addr, u64) {
+        let legacy_id = app.store_code(contract_legacy_deposit_cw20());
+        let deposit_id = app.store_code(contract_deposit_cw20());
+        let msg = LegacyInstantiateMsg { contract: contract.to_string(), version: version.to_string() };
+        let addr = app.instantiate_contract(legacy_id, Addr::unchecked(ADMIN), &msg, &[], "deposit-cw20", Some(ADMIN.to_string())).unwrap();
+        (addr, deposit_id)
+    }
+
+    #[test]
+    fn migrate_upgrades_0_1_0_state() {
+        let mut app = mock_app();
+        let (addr, deposit_id) = legacy_instantiate_as(&mut app, "deposit-cw20-example", "0.1.0");
+
+        let res = app.migrate_contract(Addr::unchecked(ADMIN), addr.clone(), &MigrateMsg { admin: Some(ADMIN.to_string()), fee_recipient: None }, deposit_id).unwrap();
+        let wasm = res.events.iter().find(|event| event.ty == "wasm").unwrap();
+        assert!(wasm.attributes.iter().any(|attr| attr.key == "from_version" && attr.value == "0.1.0"));
+
+        let info = query_contract_info(&app, addr.to_string()).unwrap();
+        assert_eq!((info.contract.as_str(), info.version.as_str()), ("deposit-cw20-example", env!("CARGO_PKG_VERSION")));
+
+        let deposit_contract = DepositContract(addr);
+        let deposits: Cw20DepositResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Cw20Deposits { address: "owner".to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(deposits.deposits[0].1.amount, Uint128::from(u128::MAX - 1));
+        let asks: AsksResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksBySeller { seller: "owner".to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(asks.asks[0].amount, Uint128::from(500u64));
+        let bids: BidsResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::Bids { cw721_contract: "nft".to_string(), token_id: "1".to_string(), start_after: None, limit: None })
+            .unwrap();
+        assert_eq!((bids.bids[0].bidder.as_str(), bids.bids[0].amount), ("bidder", Uint128::from(300u64)));
+        let ownership: OwnershipResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Ownership {}).unwrap();
+        assert_eq!(ownership.admin, Some(Addr::unchecked(ADMIN)));
+        let get_config = |app: &App| -> ConfigResponse { app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Config {}).unwrap() };
+        assert_eq!(get_config(&app).config.fee_recipient, Addr::unchecked(ADMIN));
+
+        //migrating again to the same version keeps the config
+        let msg = MigrateMsg { admin: None, fee_recipient: Some(USER.to_string()) };
+        app.migrate_contract(Addr::unchecked(ADMIN), deposit_contract.addr(), &msg, deposit_id).unwrap();
+        assert_eq!(get_config(&app).config.fee_recipient, Addr::unchecked(ADMIN));
+    }
+
+    #[test]
+    fn migrate_rejects_foreign_contracts_and_downgrades() {
+        let mut app = mock_app();
+        let (addr, deposit_id) = legacy_instantiate_as(&mut app, "crates.io:cw20-base", "0.1.0");
+        let err = app.migrate_contract(Addr::unchecked(ADMIN), addr, &MigrateMsg { admin: None, fee_recipient: None }, deposit_id).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E6000: "));
+
+        let (addr, deposit_id) = legacy_instantiate_as(&mut app, "deposit-cw20-example", "0.1.0");
+        let err = app.migrate_contract(Addr::unchecked(ADMIN), addr, &MigrateMsg { admin: None, fee_recipient: None }, deposit_id).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E6003: Migrating from 0.1.0 needs a fee recipient or an admin");
+
+        let (addr, deposit_id) = legacy_instantiate_as(&mut app, "deposit-cw20-example", "99.0.0");
+        let err = app.migrate_contract(Addr::unchecked(ADMIN), addr, &MigrateMsg { admin: None, fee_recipient: None }, deposit_id).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E6001: Cannot migrate from 99.0.0 to the older {}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn admin_handover_takes_two_steps() {
+        let (mut app, deposit_id, _, _) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let get_ownership = |app: &App| -> OwnershipResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Ownership {}).unwrap()
+        };
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        assert_eq!(get_ownership(&app).admin, Some(Addr::unchecked(ADMIN)));
+
+        let propose = |new_admin: &str, expiry: Option<Expiration>| ExecuteMsg::ProposeNewAdmin { new_admin: new_admin.to_string(), expiry };
+        let err = execute(&mut app, USER2, propose(USER2, None)).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7000: "));
+
+        //a mistyped proposal is simply replaced, the admin stays in control
+        execute(&mut app, ADMIN, propose("typo", None)).unwrap();
+        let expiry = Expiration::AtHeight(app.block_info().height + 10);
+        execute(&mut app, ADMIN, propose(USER, Some(expiry))).unwrap();
+        let ownership = get_ownership(&app);
+        assert_eq!((ownership.admin, ownership.pending_admin, ownership.pending_expiry), (Some(Addr::unchecked(ADMIN)), Some(Addr::unchecked(USER)), Some(expiry)));
+
+        let err = execute(&mut app, USER2, ExecuteMsg::AcceptAdmin {}).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7002: "));
+        app.update_block(|block| block.height += 10);
+        let err = execute(&mut app, USER, ExecuteMsg::AcceptAdmin {}).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7003: "));
+
+        execute(&mut app, ADMIN, propose(USER, None)).unwrap();
+        execute(&mut app, USER, ExecuteMsg::AcceptAdmin {}).unwrap();
+        let ownership = get_ownership(&app);
+        assert_eq!((ownership.admin, ownership.pending_admin), (Some(Addr::unchecked(USER)), None));
+        let err = execute(&mut app, USER, ExecuteMsg::AcceptAdmin {}).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7001: "));
+        execute(&mut app, ADMIN, propose(ADMIN, None)).unwrap_err();
+
+        execute(&mut app, USER, propose(USER2, None)).unwrap();
+        execute(&mut app, USER, ExecuteMsg::RenounceAdmin {}).unwrap();
+        let ownership = get_ownership(&app);
+        assert_eq!((ownership.admin, ownership.pending_admin), (None, None));
+        execute(&mut app, USER2, ExecuteMsg::AcceptAdmin {}).unwrap_err();
+        execute(&mut app, USER, propose(USER, None)).unwrap_err();
+    }
+
+    #[test]
+    fn pause_blocks_new_activity_but_not_withdrawals() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        for token_id in ["0", "1"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), 500);
+        }
+        mint_nft(&mut app, &cw721_contract, "2".to_string(), None, USER.to_string());
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid);
+        let hook_msg = Cw20HookMsg::Deposit { owner: USER.to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 10, &hook_msg);
+
+        let msg = ExecuteMsg::Pause { switches: vec![PauseSwitch::Listings, PauseSwitch::Purchases, PauseSwitch::Bids] };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg.clone()).unwrap()).unwrap_err();
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        let status: PauseStatusResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PauseStatus {}).unwrap();
+        assert_eq!(status.paused, Pauses { listings: true, purchases: true, bids: true });
+
+        let try_send = |app: &mut App, sender: &str, amount: u64, hook_msg: &Cw20HookMsg| {
+            let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(amount), msg: to_binary(hook_msg).unwrap() };
+            app.execute(Addr::unchecked(sender), cw20_contract.call(msg).unwrap()).unwrap_err().root_cause().to_string()
+        };
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: nft.clone(), token_id: "1".to_string() };
+        assert_eq!(try_send(&mut app, USER2, 500, &purchase), "E8000: Purchases are paused, withdrawals remain available");
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: "1".to_string() };
+        assert_eq!(try_send(&mut app, USER2, 100, &place_bid), "E8000: Bids are paused, withdrawals remain available");
+
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "2".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(500u64) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "2".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E8000: Listings are paused, withdrawals remain available");
+
+        let msg = ExecuteMsg::AcceptBid { cw721_contract: nft.clone(), token_id: "0".to_string(), bidder: USER2.to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap_err();
+        let simulated: SimulationResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::SimulatePurchase { cw721_contract: nft.clone(), token_id: "1".to_string(), payment_asset: cw20_contract.addr().to_string() })
+            .unwrap();
+        assert!(simulated.error.unwrap().starts_with("E8000: "));
+
+        //every escrowed asset can still be recovered
+        let msg = ExecuteMsg::WithdrawBid { contract: nft.clone(), token_id: "0".to_string() };
+        app.execute(Addr::unchecked(USER2), deposit_contract.call(msg).unwrap()).unwrap();
+        let msg = ExecuteMsg::WithdrawNft { contract: nft.clone(), token_id: "1".to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        let msg = ExecuteMsg::WithdrawCw20 { contract: cw20_contract.addr().to_string(), amount: Uint128::from(10u64) };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+
+        let msg = ExecuteMsg::Unpause { switches: vec![PauseSwitch::Bids] };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        let status: PauseStatusResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PauseStatus {}).unwrap();
+        assert_eq!(status.paused, Pauses { listings: true, purchases: true, bids: false });
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft, token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid);
+    }
+
+    #[test]
+    fn config_changes_wait_for_the_timelock() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        let get_pending = |app: &App| -> PendingChangesResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PendingChanges { start_after: None, limit: None }).unwrap()
+        };
+        let get_config = |app: &App| -> ConfigResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Config {}).unwrap()
+        };
+
+        let raise_fee = ExecuteMsg::QueueConfigChange { change: ConfigChange::SetProtocolFee { protocol_fee_bps: 500 } };
+        execute(&mut app, USER, raise_fee.clone()).unwrap_err();
+        let err = execute(&mut app, ADMIN, ExecuteMsg::QueueConfigChange { change: ConfigChange::SetProtocolFee { protocol_fee_bps: 20_000 } }).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E1000: "));
+        execute(&mut app, ADMIN, raise_fee).unwrap();
+        let allowlist = ConfigChange::SetPaymentAllowlist { assets: Some(vec![cw721_contract.addr().to_string()]) };
+        execute(&mut app, ADMIN, ExecuteMsg::QueueConfigChange { change: allowlist.clone() }).unwrap();
+
+        let pending = get_pending(&app);
+        assert_eq!(pending.changes.len(), 2);
+        assert_eq!(pending.changes[0].change, ConfigChange::SetProtocolFee { protocol_fee_bps: 500 });
+        assert_eq!(pending.changes[0].effective_at, app.block_info().time.plus_seconds(2 * 24 * 60 * 60));
 
+        let err = execute(&mut app, USER, ExecuteMsg::ApplyConfigChange { id: 1 }).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7005: "));
+        execute(&mut app, USER, ExecuteMsg::CancelConfigChange { id: 2 }).unwrap_err();
+        execute(&mut app, ADMIN, ExecuteMsg::CancelConfigChange { id: 2 }).unwrap();
+        assert_eq!(get_pending(&app).changes.len(), 1);
+
+        app.update_block(|block| block.time = block.time.plus_seconds(2 * 24 * 60 * 60));
+        execute(&mut app, USER, ExecuteMsg::ApplyConfigChange { id: 1 }).unwrap();
+        assert_eq!(get_config(&app).config.protocol_fee_bps, 500);
+        assert!(get_pending(&app).changes.is_empty());
+        let err = execute(&mut app, USER, ExecuteMsg::ApplyConfigChange { id: 2 }).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7004: "));
+
+        //a cw20 dropped from the allowlist can no longer be used for new listings
+        execute(&mut app, ADMIN, ExecuteMsg::QueueConfigChange { change: allowlist }).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(2 * 24 * 60 * 60));
+        execute(&mut app, USER, ExecuteMsg::ApplyConfigChange { id: 3 }).unwrap();
+        assert_eq!(get_config(&app).config.payment_allowlist, Some(vec![cw721_contract.addr()]));
+
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "0".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(500u64) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "0".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E1002: {} is not an allowed payment asset", cw20_contract.addr()));
+
+        //changes queued by an admin are dropped when the admin hands over or renounces
+        let curation = ExecuteMsg::QueueConfigChange { change: ConfigChange::SetCuration { enabled: true } };
+        execute(&mut app, ADMIN, curation.clone()).unwrap();
+        execute(&mut app, ADMIN, ExecuteMsg::ProposeNewAdmin { new_admin: USER.to_string(), expiry: None }).unwrap();
+        execute(&mut app, USER, ExecuteMsg::AcceptAdmin {}).unwrap();
+        assert!(get_pending(&app).changes.is_empty());
+        app.update_block(|block| block.time = block.time.plus_seconds(2 * 24 * 60 * 60));
+        let err = execute(&mut app, USER2, ExecuteMsg::ApplyConfigChange { id: 4 }).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E7004: "));
+        execute(&mut app, USER, curation).unwrap();
+        execute(&mut app, USER, ExecuteMsg::RenounceAdmin {}).unwrap();
+        assert!(get_pending(&app).changes.is_empty());
+        assert!(!get_config(&app).config.curation);
+    }
+
+    #[test]
+    fn governance_acts_through_sudo() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+
+        //governance pauses are told apart from admin ones by their attribute key
+        let action = |res: &AppResponse, key: &str| -> Option<String> {
+            let wasm = res.events.iter().find(|event| event.ty == "wasm").unwrap();
+            wasm.attributes.iter().find(|attr| attr.key == key).map(|attr| attr.value.clone())
+        };
+        let res = app.wasm_sudo(deposit_contract.addr(), &SudoMsg::Pause { switches: vec![PauseSwitch::Bids] }).unwrap();
+        assert_eq!((action(&res, "sudo"), action(&res, "execute")), (Some("pause".to_string()), None));
+        let status: PauseStatusResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PauseStatus {}).unwrap();
+        assert_eq!(status.paused, Pauses { listings: false, purchases: false, bids: true });
+        let res = app.wasm_sudo(deposit_contract.addr(), &SudoMsg::Unpause { switches: vec![PauseSwitch::Bids] }).unwrap();
+        assert_eq!((action(&res, "sudo"), action(&res, "execute")), (Some("unpause".to_string()), None));
+        let msg = ExecuteMsg::Pause { switches: vec![PauseSwitch::Bids] };
+        let res = app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        assert_eq!((action(&res, "execute"), action(&res, "sudo")), (Some("pause".to_string()), None));
+        let msg = ExecuteMsg::Unpause { switches: vec![PauseSwitch::Bids] };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+
+        //rotating the admin drops a handover and config changes the old admin started
+        let msg = ExecuteMsg::ProposeNewAdmin { new_admin: USER.to_string(), expiry: None };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        let msg = ExecuteMsg::QueueConfigChange { change: ConfigChange::SetCuration { enabled: true } };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        app.wasm_sudo(deposit_contract.addr(), &SudoMsg::SetAdmin { admin: Some(USER2.to_string()) }).unwrap();
+        let ownership: OwnershipResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Ownership {}).unwrap();
+        assert_eq!(ownership.admin, Some(Addr::unchecked(USER2)));
+        assert_eq!(ownership.pending_admin, None);
+        let pending: PendingChangesResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::PendingChanges { start_after: None, limit: None }).unwrap();
+        assert!(pending.changes.is_empty());
+        let msg = ExecuteMsg::Pause { switches: vec![PauseSwitch::Bids] };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap_err();
+
+        //fee changes skip the timelock but are still validated
+        let err = app.wasm_sudo(deposit_contract.addr(), &SudoMsg::UpdateConfig { change: ConfigChange::SetProtocolFee { protocol_fee_bps: 20_000 } }).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E1000: "));
+        app.wasm_sudo(deposit_contract.addr(), &SudoMsg::UpdateConfig { change: ConfigChange::SetProtocolFee { protocol_fee_bps: 250 } }).unwrap();
+        let config: ConfigResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Config {}).unwrap();
+        assert_eq!(config.config.protocol_fee_bps, 250);
+
+        for token_id in ["0", "1"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), 500);
+        }
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 100, &place_bid);
+
+        let msg = SudoMsg::Delist { cw721_contract: nft.clone(), token_ids: vec!["0".to_string(), "2".to_string()], reason: "stolen".to_string() };
+        let err = app.wasm_sudo(deposit_contract.addr(), &msg).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E2000: "));
+        let msg = SudoMsg::Delist { cw721_contract: nft.clone(), token_ids: vec!["0".to_string()], reason: "stolen".to_string() };
+        let res = app.wasm_sudo(deposit_contract.addr(), &msg).unwrap();
+        assert_events(&res, &deposit_contract, &[
+            ("delist", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "0".to_string()), ("seller", USER.to_string()), ("reason", "stolen".to_string())]),
+        ]);
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER.to_string());
+        let asks: AsksResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksByCollection { cw721_contract: nft.clone(), start_after: None, limit: None }).unwrap();
+        assert_eq!(asks.asks.iter().map(|ask| ask.token_id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(get_cw721_deposits(&app, &deposit_contract, &cw721_contract).deposits.len(), 1);
+
+        //the bid on the delisted token can still be withdrawn
+        let msg = ExecuteMsg::WithdrawBid { contract: nft, token_id: "0".to_string() };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn moderators_block_tokens_and_collections() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        let get_blocked = |app: &App| -> BlockedResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Blocked { start_after: None, limit: None }).unwrap()
+        };
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        for token_id in ["0", "1", "2"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), 500);
+        }
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid);
+
+        let update = ExecuteMsg::UpdateModerators { add: vec![USER2.to_string()], remove: vec![] };
+        execute(&mut app, USER2, update.clone()).unwrap_err();
+        execute(&mut app, ADMIN, update).unwrap();
+        let moderators: ModeratorsResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Moderators { start_after: None, limit: None }).unwrap();
+        assert_eq!(moderators.moderators, vec![Addr::unchecked(USER2)]);
+
+        let block = ExecuteMsg::Block { cw721_contract: nft.clone(), token_id: Some("0".to_string()), reason: "stolen".to_string() };
+        let err = execute(&mut app, USER, block.clone()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E7006: {} is not a moderator", USER));
+        let res = execute(&mut app, USER2, block).unwrap();
+        assert_events(&res, &deposit_contract, &[
+            ("block", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "0".to_string()), ("reason", "stolen".to_string()), ("moderator", USER2.to_string())]),
+            ("delist", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "0".to_string()), ("seller", USER.to_string()), ("reason", "stolen".to_string())]),
+        ]);
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER.to_string());
+
+        //the blocked token can not come back or be bid on, its standing bid can be withdrawn
+        let try_send = |app: &mut App, sender: &str, amount: u64, hook_msg: &Cw20HookMsg| {
+            let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(amount), msg: to_binary(hook_msg).unwrap() };
+            app.execute(Addr::unchecked(sender), cw20_contract.call(msg).unwrap()).unwrap_err().root_cause().to_string()
+        };
+        assert_eq!(try_send(&mut app, USER2, 200, &place_bid), format!("E8001: Token 0 of {} is blocked: stolen", nft));
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "0".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(500u64) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "0".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E8001: "));
+        execute(&mut app, USER2, ExecuteMsg::WithdrawBid { contract: nft.clone(), token_id: "0".to_string() }).unwrap();
+
+        //blocking the collection keeps its listings until anyone returns them a page at a time
+        let delist_blocked = |limit: Option<u32>| ExecuteMsg::DelistBlocked { cw721_contract: nft.clone(), limit };
+        let not_blocked = format!("E8004: Collection {} is not blocked", nft);
+        assert_eq!(execute(&mut app, USER, delist_blocked(None)).unwrap_err().root_cause().to_string(), not_blocked);
+        let res = execute(&mut app, ADMIN, ExecuteMsg::Block { cw721_contract: nft.clone(), token_id: None, reason: "exploit".to_string() }).unwrap();
+        assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-delist").count(), 0);
+        assert_eq!(get_cw721_deposits(&app, &deposit_contract, &cw721_contract).deposits.len(), 2);
+        let res = execute(&mut app, USER, delist_blocked(Some(1))).unwrap();
+        assert_events(&res, &deposit_contract, &[
+            ("delist", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "1".to_string()), ("seller", USER.to_string()), ("reason", "exploit".to_string())]),
+        ]);
+        let res = execute(&mut app, USER, delist_blocked(None)).unwrap();
+        assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-delist").count(), 1);
+        assert!(get_cw721_deposits(&app, &deposit_contract, &cw721_contract).deposits.is_empty());
+        assert_eq!(get_owner_of(&app, &cw721_contract, "2".to_string()).owner, USER.to_string());
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: nft.clone(), token_id: "1".to_string() };
+        assert_eq!(try_send(&mut app, USER2, 500, &purchase), format!("E8002: Collection {} is blocked: exploit", nft));
+        let blocked = get_blocked(&app).blocked;
+        assert_eq!(blocked.iter().map(|item| item.token_id.clone()).collect::<Vec<_>>(), vec![None, Some("0".to_string())]);
+        assert_eq!(blocked[1].blocked_by, Addr::unchecked(USER2));
+
+        let unblock = ExecuteMsg::Unblock { cw721_contract: nft.clone(), token_id: None, reason: "patched".to_string() };
+        let res = execute(&mut app, USER2, unblock.clone()).unwrap();
+        assert_events(&res, &deposit_contract, &[
+            ("unblock", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("reason", "patched".to_string()), ("moderator", USER2.to_string())]),
+        ]);
+        assert_eq!(execute(&mut app, USER2, unblock).unwrap_err().root_cause().to_string(), not_blocked);
+        let unblock = ExecuteMsg::Unblock { cw721_contract: nft.clone(), token_id: Some("1".to_string()), reason: "patched".to_string() };
+        assert_eq!(execute(&mut app, USER2, unblock).unwrap_err().root_cause().to_string(), format!("E8003: Token 1 of {} is not blocked", nft));
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "1".to_string(), 500);
+        assert_eq!(get_blocked(&app).blocked.len(), 1);
+        assert_eq!(execute(&mut app, USER, delist_blocked(None)).unwrap_err().root_cause().to_string(), not_blocked);
+
+        execute(&mut app, ADMIN, ExecuteMsg::UpdateModerators { add: vec![], remove: vec![USER2.to_string()] }).unwrap();
+        execute(&mut app, USER2, ExecuteMsg::Unblock { cw721_contract: nft, token_id: Some("0".to_string()), reason: "returned".to_string() }).unwrap_err();
+    }
+
+    #[test]
+    fn registered_collections_set_royalties_and_curation() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let unlisted_contract = cw721_instantiate(&mut app, cw721_id, "Other".to_string(), "OTH".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let other_cw20 = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        let register = |royalty_bps: u64| ExecuteMsg::RegisterCollection {
+            cw721_contract: nft.clone(),
+            name: "NFT".to_string(),
+            royalty_bps,
+            royalty_recipient: Some("creator".to_string()),
+            allowed_assets: Some(vec![cw20_contract.addr().to_string()])
+        };
+
+        let err = execute(&mut app, USER2, register(500)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E7007: {} is not the minter of {}", USER2, nft));
+        let err = execute(&mut app, USER, register(2_000)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E1003: Royalty of 2000 basis points exceeds the cap of 1000");
+        execute(&mut app, USER, register(500)).unwrap();
+        execute(&mut app, USER, ExecuteMsg::SetCollectionVerified { cw721_contract: nft.clone(), verified: true }).unwrap_err();
+        execute(&mut app, ADMIN, ExecuteMsg::SetCollectionVerified { cw721_contract: nft.clone(), verified: true }).unwrap();
+        let res: CollectionResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::Collection { cw721_contract: nft.clone() }).unwrap();
+        let collection = res.collection.unwrap();
+        assert_eq!((collection.creator, collection.verified, collection.royalty_bps), (Addr::unchecked(USER), true, 500));
+
+        //with curation on only registered collections can be listed, in their allowed assets
+        app.wasm_sudo(deposit_contract.addr(), &SudoMsg::UpdateConfig { change: ConfigChange::SetCuration { enabled: true } }).unwrap();
+        let try_deposit = |app: &mut App, cw721_contract: &NftContract, cw20_contract: &Cw20Contract, token_id: &str| {
+            mint_nft(app, cw721_contract, token_id.to_string(), None, USER.to_string());
+            let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: token_id.to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(1000u64) };
+            let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: token_id.to_string(), msg: to_binary(&hook_msg).unwrap() };
+            app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).map(|_| ()).map_err(|err| err.root_cause().to_string())
+        };
+        assert_eq!(try_deposit(&mut app, &unlisted_contract, &cw20_contract, "0"), Err(format!("E2004: Collection {} is not registered", unlisted_contract.addr())));
+        assert_eq!(try_deposit(&mut app, &cw721_contract, &other_cw20, "0"), Err(format!("E1004: {} is not an allowed payment asset for {}", other_cw20.addr(), nft)));
+        try_deposit(&mut app, &cw721_contract, &cw20_contract, "1").unwrap();
+
+        //5% of the price goes to the royalty recipient
+        let simulated: SimulationResponse = app.wrap()
+            .query_wasm_smart(deposit_contract.addr(), &QueryMsg::SimulatePurchase { cw721_contract: nft.clone(), token_id: "1".to_string(), payment_asset: cw20_contract.addr().to_string() })
+            .unwrap();
+        let breakdown = simulated.breakdown.unwrap();
+        assert_eq!((breakdown.royalty, breakdown.seller_proceeds), (Uint128::from(50u64), Uint128::from(950u64)));
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        let purchase = Cw20HookMsg::Purchase { cw721_contract: nft.clone(), token_id: "1".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 1000, &purchase);
+        assert_eq!(get_balance(&app, &cw20_contract, "creator".to_string()).balance, Uint128::from(50u64));
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::from(9950u64));
+
+        //the royalty cap and the protocol fee must fit in one sale price
+        let err = app.wasm_sudo(deposit_contract.addr(), &SudoMsg::UpdateConfig { change: ConfigChange::SetProtocolFee { protocol_fee_bps: 9_500 } }).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E1006: Royalty of 1000 and protocol fee of 9500 basis points exceed 10000 together");
+
+        //a removed collection stays out until the admin restores it
+        execute(&mut app, ADMIN, ExecuteMsg::RemoveCollection { cw721_contract: nft.clone() }).unwrap();
+        assert!(try_deposit(&mut app, &cw721_contract, &cw20_contract, "2").unwrap_err().starts_with("E2004: "));
+        let err = execute(&mut app, USER, register(500)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E2007: Collection {} was removed by the admin", nft));
+        let restore = ExecuteMsg::RestoreCollection { cw721_contract: nft.clone() };
+        assert!(execute(&mut app, USER, restore.clone()).unwrap_err().root_cause().to_string().starts_with("E7000: "));
+        execute(&mut app, ADMIN, restore).unwrap();
+        execute(&mut app, USER, register(500)).unwrap();
+        try_deposit(&mut app, &cw721_contract, &cw20_contract, "3").unwrap();
+    }
+
+    #[test]
+    fn collection_rules_override_global_trading() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate_with_fee(&mut app, deposit_id, 1_000);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        for recipient in [USER2, ADMIN] {
+            let msg = Cw20ExecuteMsg::Transfer { recipient: recipient.to_string(), amount: Uint128::from(1000u64) };
+            app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        }
+
+        let msg = ExecuteMsg::RegisterCollection { cw721_contract: nft.clone(), name: "NFT".to_string(), royalty_bps: 0, royalty_recipient: None, allowed_assets: None };
+        execute(&mut app, USER, msg).unwrap();
+        let mut rules = CollectionRules { min_price: Some(Uint128::from(100u64)), min_bid_increment_pct: Some(10), max_listing_duration: Some(3600), fee_discount_bps: None };
+        let set_rules = |rules: &CollectionRules| ExecuteMsg::SetCollectionRules { cw721_contract: nft.clone(), rules: rules.clone() };
+        let err = execute(&mut app, USER2, set_rules(&rules)).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E7008: {} is not the creator of {}", USER2, nft));
+        execute(&mut app, USER, set_rules(&rules)).unwrap();
+        //only the admin grants fee discounts
+        rules.fee_discount_bps = Some(5_000);
+        assert!(execute(&mut app, USER, set_rules(&rules)).unwrap_err().root_cause().to_string().starts_with("E7000: "));
+        execute(&mut app, ADMIN, set_rules(&CollectionRules { fee_discount_bps: Some(20_000), ..rules.clone() })).unwrap_err();
+        execute(&mut app, ADMIN, set_rules(&rules)).unwrap();
+
+        mint_nft(&mut app, &cw721_contract, "0".to_string(), None, USER.to_string());
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "0".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::from(50u64) };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "0".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        let err = app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E3002: Price of 50 is below the collection minimum of 100");
+        let listed_at = app.block_info().time;
+        let res = deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 1000);
+        assert_events(&res, &deposit_contract, &[
+            ("list", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "0".to_string()), ("seller", USER.to_string()), ("payment_asset", cw20_contract.addr().to_string()), ("price", "1000".to_string()), ("expires_at", listed_at.plus_seconds(3600).to_string())]),
+        ]);
+
+        //bids must beat the highest one by 10%
+        let place_bid = Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: "0".to_string() };
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid);
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(105u64), msg: to_binary(&place_bid).unwrap() };
+        let err = app.execute(Addr::unchecked(ADMIN), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "E4005: Bid of 105 is below the minimum next bid of 110");
+        send_cw20(&mut app, ADMIN, &cw20_contract, &deposit_contract, 110, &place_bid);
+
+        //half of the 10% protocol fee is waived
+        let simulate = QueryMsg::SimulatePurchase { cw721_contract: nft.clone(), token_id: "0".to_string(), payment_asset: cw20_contract.addr().to_string() };
+        let simulated: SimulationResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &simulate).unwrap();
+        let breakdown = simulated.breakdown.unwrap();
+        assert_eq!((breakdown.protocol_fee, breakdown.seller_proceeds), (Uint128::from(50u64), Uint128::from(950u64)));
+
+        //an expired listing can not be bought but can still be withdrawn
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        let simulated: SimulationResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &simulate).unwrap();
+        assert!(simulated.error.unwrap().starts_with("E2005: "));
+        let msg = ExecuteMsg::AcceptBid { cw721_contract: nft.clone(), token_id: "0".to_string(), bidder: ADMIN.to_string() };
+        assert!(execute(&mut app, USER, msg).unwrap_err().root_cause().to_string().starts_with("E2005: "));
+        execute(&mut app, USER, ExecuteMsg::WithdrawNft { contract: nft, token_id: "0".to_string() }).unwrap();
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER.to_string());
+    }
+
+    #[test]
+    fn expired_listings_leave_the_floor() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let execute = |app: &mut App, sender: &str, msg: ExecuteMsg| app.execute(Addr::unchecked(sender), deposit_contract.call(msg).unwrap());
+        let set_rules = |max_listing_duration: Option<u64>| ExecuteMsg::SetCollectionRules { cw721_contract: nft.clone(), rules: CollectionRules { max_listing_duration, ..CollectionRules::default() } };
+
+        let msg = ExecuteMsg::RegisterCollection { cw721_contract: nft.clone(), name: "NFT".to_string(), royalty_bps: 0, royalty_recipient: None, allowed_assets: None };
+        execute(&mut app, USER, msg).unwrap();
+        for token_id in ["0", "1"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+        }
+        //the cheaper listing expires after a minute, the other never does
+        execute(&mut app, USER, set_rules(Some(60))).unwrap();
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 100);
+        execute(&mut app, USER, set_rules(None)).unwrap();
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "1".to_string(), 200);
+
+        let floor = |app: &App| -> FloorPriceResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::FloorPrice { cw721_contract: nft.clone(), payment_asset: cw20_contract.addr().to_string() }).unwrap()
+        };
+        let by_price = |app: &App| -> Vec<String> {
+            let res: AsksResponse = app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::AsksByPrice { cw721_contract: nft.clone(), start_after: None, limit: None, order: None }).unwrap();
+            res.asks.into_iter().map(|ask| ask.token_id).collect()
+        };
+        let stats = |app: &App| -> CollectionStatsResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::CollectionStats { cw721_contract: nft.clone() }).unwrap()
+        };
+        assert_eq!(floor(&app).ask.unwrap().token_id, "0");
+        assert_eq!(by_price(&app), vec!["0", "1"]);
+        assert_eq!(stats(&app).assets[0].floor_price, Some(Uint128::from(100u64)));
+
+        app.update_block(|block| block.time = block.time.plus_seconds(60));
+        assert_eq!(floor(&app).ask.unwrap().token_id, "1");
+        assert_eq!(by_price(&app), vec!["1"]);
+        let collection = stats(&app);
+        assert_eq!((collection.listings, collection.assets[0].listings), (1, 1));
+        assert_eq!(collection.assets[0].floor_price, Some(Uint128::from(200u64)));
+
+        //anyone can hand expired listings back to their sellers
+        let res = execute(&mut app, USER2, ExecuteMsg::DelistExpired { cw721_contract: nft.clone(), limit: None }).unwrap();
+        assert_events(&res, &deposit_contract, &[
+            ("delist", vec![("version", "1".to_string()), ("cw721_contract", nft.clone()), ("token_id", "0".to_string()), ("seller", USER.to_string()), ("reason", "expired".to_string())]),
+        ]);
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER.to_string());
+        assert_eq!(get_owner_of(&app, &cw721_contract, "1".to_string()).owner, deposit_contract.addr().to_string());
+        assert_eq!(stats(&app).listings, 1);
+
+        //queries only step over a bounded number of expired listings until they are delisted
+        execute(&mut app, USER, set_rules(Some(60))).unwrap();
+        for i in 0..=MAX_EXPIRED_SKIP {
+            let token_id = format!("expiring{}", i);
+            mint_nft(&mut app, &cw721_contract, token_id.clone(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id, 100);
+        }
+        app.update_block(|block| block.time = block.time.plus_seconds(60));
+        assert_eq!(floor(&app).ask, None);
+        assert_eq!(by_price(&app), vec!["1"]);
+        assert_eq!(stats(&app).listings, 2);
+        for _ in 0..4 {
+            execute(&mut app, USER2, ExecuteMsg::DelistExpired { cw721_contract: nft.clone(), limit: Some(30) }).unwrap();
+        }
+        assert_eq!(floor(&app).ask.unwrap().token_id, "1");
+        assert_eq!(by_price(&app), vec!["1"]);
+        assert_eq!(stats(&app).listings, 1);
+    }
+
+    #[test]
+    fn bids_and_listings_match_when_they_cross() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+        let last_sale = |app: &App, token_id: &str| -> LastSaleResponse {
+            app.wrap().query_wasm_smart(deposit_contract.addr(), &QueryMsg::LastSale { cw721_contract: nft.clone(), token_id: token_id.to_string() }).unwrap()
+        };
+        for recipient in [USER2, ADMIN] {
+            let msg = Cw20ExecuteMsg::Transfer { recipient: recipient.to_string(), amount: Uint128::from(1000u64) };
+            app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        }
+        for token_id in ["0", "1", "2"] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+        }
+
+        //a bid above the ask buys at the ask and gets the difference back
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "0".to_string(), 500);
+        let place_bid = |token_id: &str| Cw20HookMsg::PlaceBid { cw721_contract: nft.clone(), token_id: token_id.to_string() };
+        let res = send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 600, &place_bid("0"));
+        let sale = res.events.iter().find(|event| event.ty == "wasm-sale").unwrap();
+        assert!(sale.attributes.iter().any(|attr| attr.key == "kind" && attr.value == "bid_match"));
+        assert_eq!(get_owner_of(&app, &cw721_contract, "0".to_string()).owner, USER2.to_string());
+        assert_eq!(get_balance(&app, &cw20_contract, USER2.to_string()).balance, Uint128::from(500u64));
+        assert_eq!(last_sale(&app, "0").sale.unwrap().price, Uint128::from(500u64));
+        assert!(get_bids(&app, &deposit_contract, &cw721_contract, "0".to_string()).bids.is_empty());
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER2);
+        let actions: Vec<(LedgerAction, u128)> = statement.entries.iter().map(|e| (e.action.clone(), e.amount.u128())).collect();
+        assert_eq!(actions, vec![(LedgerAction::Purchase, 500), (LedgerAction::Refund, 100)]);
+
+        //a listing at or below the best bid sells to that bidder at the bid
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 300, &place_bid("1"));
+        send_cw20(&mut app, ADMIN, &cw20_contract, &deposit_contract, 400, &place_bid("1"));
+        let res = deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "1".to_string(), 350);
+        assert_eq!(res.events.iter().filter(|event| event.ty == "wasm-list" || event.ty == "wasm-sale").count(), 2);
+        assert_eq!(get_owner_of(&app, &cw721_contract, "1".to_string()).owner, ADMIN.to_string());
+        let sale = last_sale(&app, "1").sale.unwrap();
+        assert_eq!((sale.buyer, sale.price), (Addr::unchecked(ADMIN), Uint128::from(400u64)));
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::from(8000u64 + 500 + 400));
+        //the outbid bid was refunded when the higher one came in
+        assert!(get_bids(&app, &deposit_contract, &cw721_contract, "1".to_string()).bids.is_empty());
+        assert_eq!(get_balance(&app, &cw20_contract, USER2.to_string()).balance, Uint128::from(500u64));
+
+        //listings above every bid stay up
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 100, &place_bid("2"));
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "2".to_string(), 200);
+        assert_eq!(get_owner_of(&app, &cw721_contract, "2".to_string()).owner, deposit_contract.addr().to_string());
+        assert!(last_sale(&app, "2").sale.is_none());
+
+        //a seller bidding on their own listing just places a bid
+        send_cw20(&mut app, USER, &cw20_contract, &deposit_contract, 250, &place_bid("2"));
+        assert_eq!(get_owner_of(&app, &cw721_contract, "2".to_string()).owner, deposit_contract.addr().to_string());
+        assert!(last_sale(&app, "2").sale.is_none());
+        let bids = get_bids(&app, &deposit_contract, &cw721_contract, "2".to_string()).bids;
+        assert!(bids.iter().any(|bid| bid.bidder == Addr::unchecked(USER) && bid.amount == Uint128::from(250u64)));
+
+        //a bid at or above an expired ask stands as a bid
+        let msg = ExecuteMsg::RegisterCollection { cw721_contract: nft.clone(), name: "NFT".to_string(), royalty_bps: 0, royalty_recipient: None, allowed_assets: None };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        let rules = CollectionRules { max_listing_duration: Some(60), ..CollectionRules::default() };
+        let msg = ExecuteMsg::SetCollectionRules { cw721_contract: nft.clone(), rules };
+        app.execute(Addr::unchecked(USER), deposit_contract.call(msg).unwrap()).unwrap();
+        mint_nft(&mut app, &cw721_contract, "3".to_string(), None, USER.to_string());
+        deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, "3".to_string(), 100);
+        app.update_block(|block| block.time = block.time.plus_seconds(60));
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 150, &place_bid("3"));
+        assert_eq!(get_owner_of(&app, &cw721_contract, "3".to_string()).owner, deposit_contract.addr().to_string());
+        assert!(last_sale(&app, "3").sale.is_none());
+        assert_eq!(get_bids(&app, &deposit_contract, &cw721_contract, "3".to_string()).bids.len(), 1);
+    }
+
+    #[test]
+    fn sweep_floor_buys_cheapest_listings_and_refunds() {
+        let (mut app, deposit_id, cw20_id, cw721_id) = store_code();
+        let deposit_contract = deposit_instantiate(&mut app, deposit_id);
+        let cw721_contract = cw721_instantiate(&mut app, cw721_id, "NFT".to_string(), "NFT".to_string(), USER.to_string());
+        let cw20_contract = cw_20_instantiate(&mut app, cw20_id);
+        let nft = cw721_contract.addr().to_string();
+
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(1000u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        for (token_id, price) in [("0", 100), ("1", 200), ("2", 300), ("3", 400), ("4", 150)] {
+            mint_nft(&mut app, &cw721_contract, token_id.to_string(), None, USER.to_string());
+            deposit_nft(&mut app, &deposit_contract, &cw721_contract, &cw20_contract, token_id.to_string(), price);
+        }
+        let sweep = |max_count: u32, max_total: u64, max_price_each: Option<u64>| Cw20HookMsg::SweepFloor {
+            cw721_contract: nft.clone(),
+            max_count,
+            max_total: Uint128::from(max_total),
+            max_price_each: max_price_each.map(Uint128::from)
+        };
+        let owner_of = |app: &App, token_id: &str| get_owner_of(app, &cw721_contract, token_id.to_string()).owner;
+
+        let res = send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 1000, &sweep(3, 1000, Some(350)));
+        let sold: Vec<_> = res.events.iter()
+            .filter(|event| event.ty == "wasm-sale")
+            .map(|event| event.attributes.iter().find(|attr| attr.key == "token_id").unwrap().value.clone())
+            .collect();
+        assert_eq!(sold, vec!["0", "4", "1"]);
+        for token_id in ["0", "1", "4"] {
+            assert_eq!(owner_of(&app, token_id), USER2.to_string());
+        }
+        assert_eq!(get_balance(&app, &cw20_contract, USER2.to_string()).balance, Uint128::from(550u64));
+        assert_eq!(get_balance(&app, &cw20_contract, USER.to_string()).balance, Uint128::from(9000u64 + 450));
+
+        //nothing fits, so the whole send fails and nothing moves
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(500u64), msg: to_binary(&sweep(5, 500, Some(250))).unwrap() };
+        let err = app.execute(Addr::unchecked(USER2), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), format!("E2006: No listing of {} fits the sweep limits", nft));
+
+        //max_total caps spending below the amount sent
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 550, &sweep(5, 300, None));
+        assert_eq!(owner_of(&app, "2"), USER2.to_string());
+        assert_eq!(owner_of(&app, "3"), deposit_contract.addr().to_string());
+        assert_eq!(get_balance(&app, &cw20_contract, USER2.to_string()).balance, Uint128::from(250u64));
+        let statement = get_statement(&app, &deposit_contract, &cw20_contract, USER2);
+        let actions: Vec<(LedgerAction, u128)> = statement.entries.iter().map(|e| (e.action.clone(), e.amount.u128())).collect();
+        assert_eq!(actions, vec![
+            (LedgerAction::Purchase, 100), (LedgerAction::Purchase, 150), (LedgerAction::Purchase, 200), (LedgerAction::Refund, 550),
+            (LedgerAction::Purchase, 300), (LedgerAction::Refund, 250)
+        ]);
+
+        //a listing priced past the budget ends the sweep instead of overflowing the total
+        mint_nft(&mut app, &cw721_contract, "5".to_string(), None, USER.to_string());
+        let hook_msg = Cw721HookMsg::Deposit { owner: USER.to_string(), token_id: "5".to_string(), cw20_contract: cw20_contract.addr().to_string(), amount: Uint128::MAX };
+        let msg = nft::contract::ExecuteMsg::SendNft { contract: deposit_contract.addr().to_string(), token_id: "5".to_string(), msg: to_binary(&hook_msg).unwrap() };
+        app.execute(Addr::unchecked(USER), cw721_contract.call(msg).unwrap()).unwrap();
+        let msg = Cw20ExecuteMsg::Transfer { recipient: USER2.to_string(), amount: Uint128::from(500u64) };
+        app.execute(Addr::unchecked(USER), cw20_contract.call(msg).unwrap()).unwrap();
+        send_cw20(&mut app, USER2, &cw20_contract, &deposit_contract, 500, &sweep(5, 500, None));
+        assert_eq!(owner_of(&app, "3"), USER2.to_string());
+        assert_eq!(owner_of(&app, "5"), deposit_contract.addr().to_string());
+        assert_eq!(get_balance(&app, &cw20_contract, USER2.to_string()).balance, Uint128::from(350u64));
+
+        //a blocked collection reports the block rather than an empty sweep
+        let msg = ExecuteMsg::Block { cw721_contract: nft.clone(), token_id: None, reason: "exploit".to_string() };
+        app.execute(Addr::unchecked(ADMIN), deposit_contract.call(msg).unwrap()).unwrap();
+        let msg = Cw20ExecuteMsg::Send { contract: deposit_contract.addr().to_string(), amount: Uint128::from(100u64), msg: to_binary(&sweep(5, 100, None)).unwrap() };
+        let err = app.execute(Addr::unchecked(USER2), cw20_contract.call(msg).unwrap()).unwrap_err();
+        assert!(err.root_cause().to_string().starts_with("E8002: "));
+    }
 }
@@ -1,7 +1,10 @@
 pub mod contract;
 mod error;
+mod events;
+mod migrations;
 pub mod msg;
 pub mod state;
+mod stats;
 pub mod helpers;
 pub mod integration_tests;
 
@@ -0,0 +1,262 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::Map;
+
+use crate::state::{
+    asks, bids, cw20_deposits, cw721_deposits, Bid, Cw20Deposits, Cw721Deposits, Offer, COLLECTION_ASSET_STATS,
+    COLLECTION_STATS, ESCROW_HOLDERS,
+};
+use crate::stats::{record_bid_placed, record_listing};
+
+// 0.1.0 stored every amount as a raw u128, which serializes as a JSON number.
+// The maps below read the same namespaces with the old layouts.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyCw20Deposits {
+    count: i32,
+    owner: Addr,
+    contract: Addr,
+    amount: u128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyOffer {
+    owner: Addr,
+    cw721_contract: Addr,
+    token_id: String,
+    cw20_contract: Addr,
+    amount: u128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyBid {
+    bidder: Addr,
+    cw721_contract: Addr,
+    token_id: String,
+    cw20_contract: Addr,
+    amount: u128
+}
+
+const LEGACY_CW20_DEPOSITS: Map<&[u8], LegacyCw20Deposits> = Map::new("cw20deposits");
+const LEGACY_ASKS: Map<&[u8], LegacyOffer> = Map::new("asks");
+const LEGACY_BIDS: Map<&[u8], LegacyBid> = Map::new("bids");
+
+const CW20_DEPOSITS_RAW: Map<&[u8], Cw20Deposits> = Map::new("cw20deposits");
+const ASKS_RAW: Map<&[u8], Offer> = Map::new("asks");
+const BIDS_RAW: Map<&[u8], Bid> = Map::new("bids");
+
+/// Rewrites every stored u128 amount as a Uint128. Entries already in the
+/// new layout fail to parse as legacy ones and are left untouched, so running
+/// this more than once is harmless.
+pub fn migrate_u128_amounts(storage: &mut dyn Storage) -> StdResult<()> {
+    rewrite(storage, LEGACY_CW20_DEPOSITS, CW20_DEPOSITS_RAW, |old| Cw20Deposits {
+        count: old.count,
+        owner: old.owner,
+        contract: old.contract,
+        amount: Uint128::from(old.amount)
+    })?;
+    rewrite(storage, LEGACY_ASKS, ASKS_RAW, |old| Offer {
+        owner: old.owner,
+        cw721_contract: old.cw721_contract,
+        token_id: old.token_id,
+        cw20_contract: old.cw20_contract,
+        amount: Uint128::from(old.amount),
+        expires_at: None
+    })?;
+    rewrite(storage, LEGACY_BIDS, BIDS_RAW, |old| Bid {
+        bidder: old.bidder,
+        cw721_contract: old.cw721_contract,
+        token_id: old.token_id,
+        cw20_contract: old.cw20_contract,
+        amount: Uint128::from(old.amount)
+    })
+}
+
+// 0.1.0 kept a single bid per token under (cw721 contract, token_id)
+const LEGACY_TOKEN_BIDS: Map<(&str, &str), Bid> = Map::new("bids");
+
+/// Moves bids from the legacy (cw721 contract, token_id) key to the
+/// (cw721 contract, token_id, bidder) key of the indexed bids map.
+pub fn rekey_bids(storage: &mut dyn Storage) -> StdResult<()> {
+    let legacy: Vec<Bid> = LEGACY_TOKEN_BIDS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect::<StdResult<_>>()?;
+    for bid in legacy {
+        bids().save(storage, (&bid.cw721_contract, &bid.token_id, &bid.bidder), &bid)?;
+        LEGACY_TOKEN_BIDS.remove(storage, (bid.cw721_contract.as_str(), &bid.token_id));
+    }
+    Ok(())
+}
+
+/// Saves every deposit and ask again so that their secondary indexes are
+/// written for entries stored before the maps were indexed.
+pub fn rebuild_indexes(storage: &mut dyn Storage) -> StdResult<()> {
+    let deposits: Vec<Cw20Deposits> = cw20_deposits()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, deposit)| deposit))
+        .collect::<StdResult<_>>()?;
+    for deposit in deposits {
+        cw20_deposits().save(storage, (&deposit.owner, &deposit.contract), &deposit)?;
+    }
+
+    let deposits: Vec<Cw721Deposits> = cw721_deposits()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, deposit)| deposit))
+        .collect::<StdResult<_>>()?;
+    for deposit in deposits {
+        cw721_deposits().save(storage, (&deposit.contract, &deposit.owner, &deposit.token_id), &deposit)?;
+    }
+
+    let offers: Vec<Offer> = asks()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<_>>()?;
+    for offer in offers {
+        asks().save(storage, (&offer.cw721_contract, &offer.token_id), &offer)?;
+    }
+
+    let standing: Vec<Bid> = bids()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect::<StdResult<_>>()?;
+    for bid in standing {
+        bids().save(storage, (&bid.cw721_contract, &bid.token_id, &bid.bidder), &bid)?;
+    }
+    Ok(())
+}
+
+/// Recounts listings, escrow holders and standing bids of every collection from the
+/// stored asks and bids. Sales and volumes are only ever added by settlement.
+pub fn backfill_collection_stats(storage: &mut dyn Storage) -> StdResult<()> {
+    let collections = COLLECTION_STATS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (collection, mut stats) in collections {
+        stats.listings = 0;
+        stats.escrow_holders = 0;
+        COLLECTION_STATS.save(storage, &collection, &stats)?;
+    }
+    let per_asset = COLLECTION_ASSET_STATS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((collection, asset), mut stats) in per_asset {
+        stats.listings = 0;
+        stats.bids = 0;
+        COLLECTION_ASSET_STATS.save(storage, (&collection, &asset), &stats)?;
+    }
+    let holders = ESCROW_HOLDERS
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (collection, owner) in holders {
+        ESCROW_HOLDERS.remove(storage, (&collection, &owner));
+    }
+
+    let offers: Vec<Offer> = asks()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<_>>()?;
+    for offer in offers {
+        record_listing(storage, &offer)?;
+    }
+    let standing: Vec<Bid> = bids()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect::<StdResult<_>>()?;
+    for bid in standing {
+        record_bid_placed(storage, &bid)?;
+    }
+    Ok(())
+}
+
+fn rewrite<L, T>(storage: &mut dyn Storage, legacy: Map<&[u8], L>, current: Map<&[u8], T>, convert: fn(L) -> T) -> StdResult<()>
+where
+    L: Serialize + DeserializeOwned,
+    T: Serialize + DeserializeOwned,
+{
+    let keys: Vec<Vec<u8>> = legacy.keys_raw(storage, None, None, Order::Ascending).collect();
+    for key in keys {
+        if let Ok(old) = legacy.load(storage, &key) {
+            current.save(storage, &key, &convert(old))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the 0.1.0 layout: a deposit of `u128::MAX - 1` cw20 by "owner", and token "1"
+/// of "nft" escrowed by "owner" with an ask of 500 and a bid of 300 from "bidder".
+#[cfg(test)]
+pub(crate) fn save_legacy_state(storage: &mut dyn Storage) -> StdResult<()> {
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct LegacyCw721Deposits {
+        owner: Addr,
+        contract: Addr,
+        token_id: String
+    }
+
+    let legacy_deposits: Map<(&str, &str), LegacyCw20Deposits> = Map::new("cw20deposits");
+    let legacy_nfts: Map<(&str, &str, &str), LegacyCw721Deposits> = Map::new("cw721deposits");
+    let legacy_asks: Map<(&str, &str), LegacyOffer> = Map::new("asks");
+    let legacy_bids: Map<(&str, &str), LegacyBid> = Map::new("bids");
+
+    legacy_deposits.save(storage, ("owner", "cw20"), &LegacyCw20Deposits {
+        count: 2, owner: Addr::unchecked("owner"), contract: Addr::unchecked("cw20"), amount: u128::MAX - 1
+    })?;
+    legacy_nfts.save(storage, ("nft", "owner", "1"), &LegacyCw721Deposits {
+        owner: Addr::unchecked("owner"), contract: Addr::unchecked("nft"), token_id: "1".to_string()
+    })?;
+    legacy_asks.save(storage, ("nft", "1"), &LegacyOffer {
+        owner: Addr::unchecked("owner"), cw721_contract: Addr::unchecked("nft"), token_id: "1".to_string(), cw20_contract: Addr::unchecked("cw20"), amount: 500
+    })?;
+    legacy_bids.save(storage, ("nft", "1"), &LegacyBid {
+        bidder: Addr::unchecked("bidder"), cw721_contract: Addr::unchecked("nft"), token_id: "1".to_string(), cw20_contract: Addr::unchecked("cw20"), amount: 300
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::mock_dependencies;
+
+    #[test]
+    fn migrates_legacy_state() {
+        let mut deps = mock_dependencies();
+        save_legacy_state(deps.as_mut().storage).unwrap();
+        let big = u128::MAX - 1;
+
+        // a second run must leave migrated entries alone
+        for _ in 0..2 {
+            migrate_u128_amounts(deps.as_mut().storage).unwrap();
+            rekey_bids(deps.as_mut().storage).unwrap();
+            rebuild_indexes(deps.as_mut().storage).unwrap();
+            backfill_collection_stats(deps.as_mut().storage).unwrap();
+        }
+
+        let owner = Addr::unchecked("owner");
+        let nft = Addr::unchecked("nft");
+        let cw20 = Addr::unchecked("cw20");
+
+        let deposit = cw20_deposits().load(deps.as_ref().storage, (&owner, &cw20)).unwrap();
+        assert_eq!(deposit.amount, Uint128::from(big));
+        assert_eq!(deposit.count, 2);
+        let ask = asks().load(deps.as_ref().storage, (&nft, "1")).unwrap();
+        assert_eq!(ask.amount, Uint128::from(500u64));
+        let bid = bids().load(deps.as_ref().storage, (&nft, "1", &Addr::unchecked("bidder"))).unwrap();
+        assert_eq!(bid.amount, Uint128::from(300u64));
+        assert!(!LEGACY_TOKEN_BIDS.has(deps.as_ref().storage, ("nft", "1")));
+
+        let by_seller = asks().idx.seller.prefix(owner.clone()).keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+        assert_eq!(by_seller, 1);
+        let by_owner = cw721_deposits().idx.owner.prefix(owner).keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+        assert_eq!(by_owner, 1);
+        let by_bidder = bids().idx.bidder.prefix(Addr::unchecked("bidder")).keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+        assert_eq!(by_bidder, 1);
+
+        let stats = COLLECTION_STATS.load(deps.as_ref().storage, &nft).unwrap();
+        assert_eq!((stats.listings, stats.escrow_holders), (1, 1));
+        let stats = COLLECTION_ASSET_STATS.load(deps.as_ref().storage, (&nft, &cw20)).unwrap();
+        assert_eq!((stats.listings, stats.bids), (1, 1));
+    }
+}
@@ -1,14 +1,21 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use cosmwasm_std::{Addr, Uint128};
 use cw20::Cw20ReceiveMsg;
 use cw721::Cw721ReceiveMsg;
+use cw_utils::Expiration;
 
-use crate::state::{Cw20Deposits, Cw721Deposits, Bid};
+use crate::state::{Cw20Deposits, Cw721Deposits, Bid, BlockedItem, Collection, CollectionRules, Config, ConfigChange, LedgerEntry, Offer, Pauses, PendingChange, Sale};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct InstantiateMsg {
-
+    /// Share of each sale taken by the marketplace, in basis points
+    pub protocol_fee_bps: u64,
+    /// Receives the protocol fee, defaults to the instantiating address
+    pub fee_recipient: Option<String>,
+    /// Defaults to the instantiating address
+    pub admin: Option<String>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -18,14 +25,139 @@ pub enum ExecuteMsg {
     ReceiveNft(Cw721ReceiveMsg),
     WithdrawNft { contract: String, token_id: String },
     WithdrawBid { contract: String, token_id: String },
+    WithdrawCw20 { contract: String, amount: Uint128 },
+    /// Sells an escrowed NFT to one of its bidders, paid with the locked bid
+    AcceptBid { cw721_contract: String, token_id: String, bidder: String },
+    /// Offers the admin role to `new_admin`, replacing any earlier proposal
+    ProposeNewAdmin { new_admin: String, expiry: Option<Expiration> },
+    /// Takes over the admin role proposed to the sender
+    AcceptAdmin {},
+    /// Leaves the contract without an admin for good
+    RenounceAdmin {},
+    /// Stops new activity of the given kinds; withdrawals stay open
+    Pause { switches: Vec<PauseSwitch> },
+    Unpause { switches: Vec<PauseSwitch> },
+    /// Queues a config change that can be applied once the timelock has passed. Queued changes
+    /// are dropped when the admin changes or is renounced.
+    QueueConfigChange { change: ConfigChange },
+    /// Applies a queued change whose timelock has passed, callable by anyone
+    ApplyConfigChange { id: u64 },
+    CancelConfigChange { id: u64 },
+    UpdateModerators { add: Vec<String>, remove: Vec<String> },
+    /// Registers a collection or updates its entry. Only the minter reported by the cw721
+    /// `Minter` query can do this; `royalty_recipient` defaults to the minter.
+    RegisterCollection { cw721_contract: String, name: String, royalty_bps: u64, royalty_recipient: Option<String>, allowed_assets: Option<Vec<String>> },
+    /// Replaces the trading rules of a registered collection. Callable by its creator or the
+    /// admin; only the admin can change the fee discount.
+    SetCollectionRules { cw721_contract: String, rules: CollectionRules },
+    SetCollectionVerified { cw721_contract: String, verified: bool },
+    /// Removes a collection. Its minter can not register it again until the admin restores it.
+    RemoveCollection { cw721_contract: String },
+    /// Lets the minter of a removed collection register it again
+    RestoreCollection { cw721_contract: String },
+    /// Blocks a token, or the whole collection when `token_id` is None. A blocked token's listing
+    /// is returned right away; a blocked collection's listings are returned by `DelistBlocked`.
+    Block { cw721_contract: String, token_id: Option<String>, reason: String },
+    Unblock { cw721_contract: String, token_id: Option<String>, reason: String },
+    /// Returns up to `limit` expired listings of a collection to their sellers, callable by anyone
+    DelistExpired { cw721_contract: String, limit: Option<u32> },
+    /// Returns up to `limit` listings of a blocked collection to their sellers, callable by anyone
+    DelistBlocked { cw721_contract: String, limit: Option<u32> },
+}
+
+/// Messages only the chain itself can send, through governance. They take effect
+/// immediately and do not need the admin key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    Pause { switches: Vec<PauseSwitch> },
+    Unpause { switches: Vec<PauseSwitch> },
+    /// Replaces the admin and drops any pending handover; `None` leaves the contract without one
+    SetAdmin { admin: Option<String> },
+    /// Applies a config change without the timelock
+    UpdateConfig { change: ConfigChange },
+    /// Cancels the asks of the given tokens and returns them to their escrow owners
+    Delist { cw721_contract: String, token_ids: Vec<String>, reason: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseSwitch {
+    /// NFT deposits, which always list the token
+    Listings,
+    /// Purchases and accepted bids
+    Purchases,
+    Bids
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    Cw20Deposits { address: String },
-    Cw721Deposits { address: String, contract:String },
-    Bids { cw721_contract: String, token_id: String }
+    Cw20Deposits { address: String, start_after: Option<String>, limit: Option<u32> },
+    Cw721Deposits { address: String, contract:String, start_after: Option<String>, limit: Option<u32> },
+    Bids { cw721_contract: String, token_id: String, start_after: Option<String>, limit: Option<u32> },
+    /// Asks of every collection, ordered by (cw721_contract, token_id)
+    AllAsks { start_after: Option<(String, String)>, limit: Option<u32> },
+    /// Asks of a single collection, ordered by token_id
+    AsksByCollection { cw721_contract: String, start_after: Option<String>, limit: Option<u32> },
+    /// Asks of a single collection sorted by amount, then token_id. Amounts in different
+    /// payment assets are compared as raw numbers. Expired asks are left out, at most 100 of them
+    /// per page; `DelistExpired` clears the rest.
+    AsksByPrice { cw721_contract: String, start_after: Option<(Uint128, String)>, limit: Option<u32>, order: Option<OrderBy> },
+    /// Asks of a seller across collections, ordered by (cw721_contract, token_id)
+    AsksBySeller { seller: String, start_after: Option<(String, String)>, limit: Option<u32> },
+    /// Standing bids of a bidder across collections, ordered by (cw721_contract, token_id)
+    BidsByBidder { bidder: String, start_after: Option<(String, String)>, limit: Option<u32> },
+    /// Escrowed NFTs, live asks, cw20 deposits and standing bids of an address in one call.
+    /// Each list holds at most one full page, `has_more` flags the ones the paginated queries
+    /// have more of.
+    Portfolio { address: String },
+    /// Sales of a token, newest first
+    SalesByToken { cw721_contract: String, token_id: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Sales of a collection, newest first
+    SalesByCollection { cw721_contract: String, start_after: Option<u64>, limit: Option<u32> },
+    LastSale { cw721_contract: String, token_id: String },
+    /// Fee breakdown of buying a listed token, or the error the purchase would fail with
+    SimulatePurchase { cw721_contract: String, token_id: String, payment_asset: String },
+    /// Fee breakdown of the seller accepting a bid, or the error it would fail with
+    SimulateAcceptBid { cw721_contract: String, token_id: String, bidder: String },
+    /// Owner, ask, ranked bids and last sale of a token in one response
+    TokenMarket { cw721_contract: String, token_id: String },
+    /// Listing, sale, volume and escrow aggregates of a collection. Listing counts leave out up
+    /// to 100 expired asks that have not been delisted yet.
+    CollectionStats { cw721_contract: String },
+    /// Time-weighted average sale price of a collection over the last `window` seconds
+    CollectionTwap { cw721_contract: String, window: u64 },
+    /// Trading totals of an address per payment asset
+    UserStats { address: String },
+    /// Traders ranked by volume in a payment asset over the last `days` days
+    Leaderboard { payment_asset: String, days: u64, limit: Option<u32> },
+    /// Cheapest unexpired ask of a collection priced in the given cw20. None as well when more
+    /// than 100 cheaper asks have expired without being delisted.
+    FloorPrice { cw721_contract: String, payment_asset: String },
+    /// Ledger entries of an address for a cw20 contract, oldest first
+    Statement { address: String, contract: String, start_after: Option<u64>, limit: Option<u32> },
+    /// Current admin and any pending handover
+    Ownership {},
+    /// Which kinds of activity are paused
+    PauseStatus {},
+    Config {},
+    /// Queued config changes, oldest first
+    PendingChanges { start_after: Option<u64>, limit: Option<u32> },
+    Collection { cw721_contract: String },
+    /// Registered collections, ordered by cw721_contract
+    Collections { start_after: Option<String>, limit: Option<u32> },
+    Moderators { start_after: Option<String>, limit: Option<u32> },
+    /// Blocked collections and tokens, ordered by (cw721_contract, token_id). A blocked
+    /// collection sorts before its tokens.
+    Blocked { start_after: Option<(String, Option<String>)>, limit: Option<u32> }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderBy {
+    Ascending,
+    Descending
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -43,23 +175,252 @@ pub struct Cw721DepositResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct BidsResponse {
-    pub bids: Option<Bid>
+    pub bids: Vec<Bid>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AsksResponse {
+    pub asks: Vec<Offer>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PortfolioResponse {
+    pub escrowed: Vec<Cw721Deposits>,
+    /// Live asks only, expired ones are left out
+    pub asks: Vec<Offer>,
+    pub deposits: Vec<Cw20Deposits>,
+    pub bids: Vec<Bid>,
+    /// Which lists were cut off at a full page
+    pub has_more: PortfolioHasMore
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct PortfolioHasMore {
+    /// Continue with `Cw721Deposits` per collection
+    pub escrowed: bool,
+    /// Continue with `AsksBySeller`
+    pub asks: bool,
+    /// Continue with `Cw20Deposits`
+    pub deposits: bool,
+    /// Continue with `BidsByBidder`
+    pub bids: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FloorPriceResponse {
+    pub ask: Option<Offer>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SalesResponse {
+    pub sales: Vec<Sale>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LastSaleResponse {
+    pub sale: Option<Sale>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SimulationResponse {
+    pub breakdown: Option<SaleBreakdown>,
+    pub error: Option<String>
+}
+
+/// Where the price of a sale goes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SaleBreakdown {
+    pub payment_asset: Addr,
+    pub price: Uint128,
+    pub protocol_fee: Uint128,
+    pub royalty: Uint128,
+    pub seller_proceeds: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenMarketResponse {
+    /// Depositor while the token is in escrow, otherwise its owner on the cw721 contract
+    pub owner: Addr,
+    pub escrowed: bool,
+    pub ask: Option<Offer>,
+    /// Highest bid first
+    pub bids: Vec<Bid>,
+    pub last_sale: Option<Sale>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionStatsResponse {
+    pub cw721_contract: Addr,
+    pub listings: u64,
+    pub sales: u64,
+    pub escrow_holders: u64,
+    /// Figures per payment asset, since amounts in different cw20s do not add up
+    pub assets: Vec<AssetStatsResponse>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AssetStatsResponse {
+    pub payment_asset: Addr,
+    pub listings: u64,
+    pub floor_price: Option<Uint128>,
+    /// Highest standing bid on any token of the collection
+    pub best_offer: Option<Uint128>,
+    pub sales: u64,
+    pub volume: Uint128,
+    /// Volume of the current hour and the 23 before it
+    pub volume_24h: Uint128,
+    /// Volume of the current day and the 6 before it
+    pub volume_7d: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionTwapResponse {
+    pub cw721_contract: Addr,
+    pub window: u64,
+    /// One entry per payment asset the collection has sold in
+    pub twaps: Vec<AssetTwapResponse>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AssetTwapResponse {
+    pub payment_asset: Addr,
+    pub twap: Uint128,
+    /// Seconds the average covers, shorter than the window for recently traded collections
+    pub observed: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct UserStatsResponse {
+    pub address: Addr,
+    pub assets: Vec<UserAssetStats>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct UserAssetStats {
+    pub payment_asset: Addr,
+    pub volume_bought: Uint128,
+    pub volume_sold: Uint128,
+    pub trades: u64,
+    pub fees_paid: Uint128,
+    pub royalties_paid: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LeaderboardResponse {
+    pub payment_asset: Addr,
+    pub days: u64,
+    pub traders: Vec<TraderVolume>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TraderVolume {
+    pub address: Addr,
+    /// Volume bought plus volume sold
+    pub volume: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConfigResponse {
+    pub config: Config
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingChangesResponse {
+    pub changes: Vec<PendingChange>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum MigrateMsg {}
+pub struct CollectionResponse {
+    pub collection: Option<Collection>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionsResponse {
+    pub collections: Vec<Collection>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModeratorsResponse {
+    pub moderators: Vec<Addr>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BlockedResponse {
+    pub blocked: Vec<BlockedItem>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PauseStatusResponse {
+    pub paused: Pauses
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct OwnershipResponse {
+    pub admin: Option<Addr>,
+    pub pending_admin: Option<Addr>,
+    pub pending_expiry: Option<Expiration>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct StatementResponse {
+    pub entries: Vec<LedgerEntry>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    /// Admin to install when upgrading a contract that predates admins, ignored otherwise
+    pub admin: Option<String>,
+    /// Receives protocol fees when upgrading a contract that predates fees, defaults to the
+    /// admin. Ignored otherwise.
+    pub fee_recipient: Option<String>
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
-    Deposit { owner:String, amount:u128 },
+    /// Credits the sent amount to `owner`
+    Deposit { owner:String },
     Purchase { cw721_contract:String, token_id:String },
-    PlaceBid { cw721_contract:String, token_id:String }
+    /// Bids the sent amount. A token holds one standing bid per payment asset, so a bid that
+    /// beats the current one in the same cw20 refunds it.
+    PlaceBid { cw721_contract:String, token_id:String },
+    /// Buys the cheapest listings of a collection priced in the sent cw20, cheapest first, until
+    /// `max_count` tokens are bought (at most 30), the next one would push the total over
+    /// `max_total` or the sent amount, or costs more than `max_price_each`. At most 100 listings
+    /// are looked at, including the buyer's own and expired ones that get skipped. Unspent funds
+    /// are refunded.
+    SweepFloor { cw721_contract:String, max_count:u32, max_total:Uint128, max_price_each:Option<Uint128> }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw721HookMsg {
-    Deposit { owner:String, token_id:String, cw20_contract:String, amount:u128 }
+    Deposit { owner:String, token_id:String, cw20_contract:String, amount:Uint128 }
 }
@@ -1,49 +1,401 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Coin};
-use cw_storage_plus::{Map, Item};
+use cosmwasm_std::{Addr, Empty, Timestamp, Uint128, Uint256};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::Expiration;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// Share of each sale taken by the marketplace, in basis points
+    pub protocol_fee_bps: u64,
+    pub fee_recipient: Addr,
+    /// Highest royalty a collection may charge, in basis points
+    #[serde(default = "default_max_royalty_bps")]
+    pub max_royalty_bps: u64,
+    /// Cw20 contracts accepted for new listings and bids, None accepts any
+    #[serde(default)]
+    pub payment_allowlist: Option<Vec<Addr>>,
+    /// Only registered collections can be listed when set
+    #[serde(default)]
+    pub curation: bool
+}
+
+pub const DEFAULT_MAX_ROYALTY_BPS: u64 = 1_000;
+
+fn default_max_royalty_bps() -> u64 {
+    DEFAULT_MAX_ROYALTY_BPS
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+/// An admin change to the config. Changes are queued and only apply after a delay.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChange {
+    SetProtocolFee { protocol_fee_bps: u64 },
+    SetFeeRecipient { fee_recipient: String },
+    SetMaxRoyalty { max_royalty_bps: u64 },
+    /// Replaces the whole allowlist, None accepts any cw20
+    SetPaymentAllowlist { assets: Option<Vec<String>> },
+    SetCuration { enabled: bool },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingChange {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub queued_at: Timestamp,
+    /// The change can be applied from this time on
+    pub effective_at: Timestamp
+}
+
+pub const PENDING_CHANGES: Map<u64, PendingChange> = Map::new("pending_changes");
+pub const CHANGE_SEQ: Item<u64> = Item::new("change_seq");
+
+//None once the admin has been renounced
+pub const ADMIN: Item<Option<Addr>> = Item::new("admin");
+
+/// Admin handover waiting for the proposed address to accept it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingAdmin {
+    pub new_admin: Addr,
+    pub expiry: Option<Expiration>
+}
+
+pub const PENDING_ADMIN: Item<PendingAdmin> = Item::new("pending_admin");
+
+/// Switches the admin can flip to stop new activity. Withdrawals are never paused.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Pauses {
+    pub listings: bool,
+    pub purchases: bool,
+    pub bids: bool
+}
+
+pub const PAUSES: Item<Pauses> = Item::new("pauses");
+
+/// A collection registered by its minter. The royalty is paid out of every sale, capped by
+/// `Config::max_royalty_bps`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Collection {
+    pub cw721_contract: Addr,
+    pub name: String,
+    pub creator: Addr,
+    /// Set by the admin once the collection has been reviewed
+    pub verified: bool,
+    pub royalty_bps: u64,
+    pub royalty_recipient: Addr,
+    /// Cw20 contracts accepted for this collection; an asset must also be on the global allowlist.
+    /// None accepts any asset the allowlist does
+    pub allowed_assets: Option<Vec<Addr>>,
+    #[serde(default)]
+    pub rules: CollectionRules
+}
+
+/// Trading rules of a collection that override the global behaviour. Unset rules do not apply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct CollectionRules {
+    /// Lowest ask a new listing may have
+    pub min_price: Option<Uint128>,
+    /// A new bid must beat the highest bid by at least this percentage
+    pub min_bid_increment_pct: Option<u64>,
+    /// Seconds after which a new listing can no longer be bought; the owner can still withdraw it
+    pub max_listing_duration: Option<u64>,
+    /// Share of the protocol fee waived for this collection, in basis points. Only the admin sets it.
+    pub fee_discount_bps: Option<u64>
+}
+
+pub const COLLECTIONS: Map<&Addr, Collection> = Map::new("collections");
+
+//collections removed by the admin, their minters can not register them again until restored
+pub const REMOVED_COLLECTIONS: Map<&Addr, Empty> = Map::new("removed_collections");
+
+//managed by the admin, who can always moderate as well
+pub const MODERATORS: Map<&Addr, Empty> = Map::new("moderators");
+
+/// A token, or a whole collection when `token_id` is None, taken off the market by a moderator.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlockedItem {
+    pub cw721_contract: Addr,
+    pub token_id: Option<String>,
+    pub reason: String,
+    pub blocked_by: Addr,
+    pub blocked_at: Timestamp
+}
+
+//kinds of block, kept in the key so a collection block can not collide with any token id
+pub const COLLECTION_BLOCK: u8 = 0;
+pub const TOKEN_BLOCK: u8 = 1;
+
+//key is cw721 contract, kind of block, token_id (empty for a collection block)
+pub const BLOCKED: Map<(&Addr, u8, &str), BlockedItem> = Map::new("blocks");
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Cw20Deposits {
     pub count: i32,
-    pub owner: String,
-    pub contract:String,
-    pub amount:u128
+    pub owner: Addr,
+    pub contract:Addr,
+    pub amount:Uint128
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Cw721Deposits {
-    pub owner: String,
-    pub contract:String,
+    pub owner: Addr,
+    pub contract:Addr,
     pub token_id:String
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Offer {
-    pub owner:String,
-    pub cw721_contract:String,
+    pub owner:Addr,
+    pub cw721_contract:Addr,
     pub token_id: String,
-    pub cw20_contract:String,
-    pub amount: u128
+    pub cw20_contract:Addr,
+    pub amount: Uint128,
+    /// Set when the collection limits how long listings last
+    #[serde(default)]
+    pub expires_at: Option<Timestamp>
+}
+
+impl Offer {
+    /// Expired asks stay in escrow until withdrawn or delisted, but can no longer be bought.
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expires_at.map(|expires_at| now >= expires_at).unwrap_or(false)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Bid {
-    pub bidder:String,
-    pub cw721_contract:String,
+    pub bidder:Addr,
+    pub cw721_contract:Addr,
     pub token_id: String,
-    pub cw20_contract:String,
-    pub amount: u128
+    pub cw20_contract:Addr,
+    pub amount: Uint128
+}
+
+pub struct Cw20DepositIndexes<'a> {
+    pub contract: MultiIndex<'a, Addr, Cw20Deposits, (Addr, Addr)>,
+}
+
+impl<'a> IndexList<Cw20Deposits> for Cw20DepositIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Cw20Deposits>> + '_> {
+        let v: Vec<&dyn Index<Cw20Deposits>> = vec![&self.contract];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is owner, cw20 contract
+pub fn cw20_deposits<'a>() -> IndexedMap<'a, (&'a Addr, &'a Addr), Cw20Deposits, Cw20DepositIndexes<'a>> {
+    let indexes = Cw20DepositIndexes {
+        contract: MultiIndex::new(|d: &Cw20Deposits| d.contract.clone(), "cw20deposits", "cw20deposits__contract"),
+    };
+    IndexedMap::new("cw20deposits", indexes)
+}
+
+pub struct Cw721DepositIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, Cw721Deposits, (Addr, Addr, String)>,
 }
 
-//key is address, denom
-pub const CW20_DEPOSITS: Map<(&str, &str), Cw20Deposits> = Map::new("cw20deposits");
+impl<'a> IndexList<Cw721Deposits> for Cw721DepositIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Cw721Deposits>> + '_> {
+        let v: Vec<&dyn Index<Cw721Deposits>> = vec![&self.owner];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is cw721 contract, owner, token_id
+pub fn cw721_deposits<'a>() -> IndexedMap<'a, (&'a Addr, &'a Addr, &'a str), Cw721Deposits, Cw721DepositIndexes<'a>> {
+    let indexes = Cw721DepositIndexes {
+        owner: MultiIndex::new(|d: &Cw721Deposits| d.owner.clone(), "cw721deposits", "cw721deposits__owner"),
+    };
+    IndexedMap::new("cw721deposits", indexes)
+}
+
+pub struct AskIndexes<'a> {
+    pub seller: MultiIndex<'a, Addr, Offer, (Addr, String)>,
+    pub collection: MultiIndex<'a, Addr, Offer, (Addr, String)>,
+    pub price: MultiIndex<'a, (Addr, u128), Offer, (Addr, String)>,
+    pub asset_price: MultiIndex<'a, (Addr, Addr, u128), Offer, (Addr, String)>,
+    pub expiry: MultiIndex<'a, (Addr, u64), Offer, (Addr, String)>,
+}
+
+impl<'a> IndexList<Offer> for AskIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Offer>> + '_> {
+        let v: Vec<&dyn Index<Offer>> = vec![&self.seller, &self.collection, &self.price, &self.asset_price, &self.expiry];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is cw721 contract, token_id
+pub fn asks<'a>() -> IndexedMap<'a, (&'a Addr, &'a str), Offer, AskIndexes<'a>> {
+    let indexes = AskIndexes {
+        seller: MultiIndex::new(|o: &Offer| o.owner.clone(), "asks", "asks__seller"),
+        collection: MultiIndex::new(|o: &Offer| o.cw721_contract.clone(), "asks", "asks__collection"),
+        price: MultiIndex::new(|o: &Offer| (o.cw721_contract.clone(), o.amount.u128()), "asks", "asks__price"),
+        asset_price: MultiIndex::new(|o: &Offer| (o.cw721_contract.clone(), o.cw20_contract.clone(), o.amount.u128()), "asks", "asks__asset_price"),
+        //asks without an expiry sort last
+        expiry: MultiIndex::new(|o: &Offer| (o.cw721_contract.clone(), o.expires_at.map(|t| t.nanos()).unwrap_or(u64::MAX)), "asks", "asks__expiry"),
+    };
+    IndexedMap::new("asks", indexes)
+}
+
+pub struct BidIndexes<'a> {
+    pub bidder: MultiIndex<'a, Addr, Bid, (Addr, String, Addr)>,
+    pub collection: MultiIndex<'a, Addr, Bid, (Addr, String, Addr)>,
+    pub asset_price: MultiIndex<'a, (Addr, Addr, u128), Bid, (Addr, String, Addr)>,
+}
+
+impl<'a> IndexList<Bid> for BidIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Bid>> + '_> {
+        let v: Vec<&dyn Index<Bid>> = vec![&self.bidder, &self.collection, &self.asset_price];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is cw721 contract, token_id, bidder
+pub fn bids<'a>() -> IndexedMap<'a, (&'a Addr, &'a str, &'a Addr), Bid, BidIndexes<'a>> {
+    let indexes = BidIndexes {
+        bidder: MultiIndex::new(|b: &Bid| b.bidder.clone(), "token_bids", "token_bids__bidder"),
+        collection: MultiIndex::new(|b: &Bid| b.cw721_contract.clone(), "token_bids", "token_bids__collection"),
+        asset_price: MultiIndex::new(|b: &Bid| (b.cw721_contract.clone(), b.cw20_contract.clone(), b.amount.u128()), "token_bids", "token_bids__asset_price"),
+    };
+    IndexedMap::new("token_bids", indexes)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerAction {
+    Deposit,
+    Withdrawal,
+    Purchase,
+    BidLock,
+    Refund,
+    Proceeds
+}
+
+/// A single movement of cw20 funds held or routed by the contract on behalf of an address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LedgerEntry {
+    pub id: u64,
+    pub action: LedgerAction,
+    pub amount: Uint128,
+    pub cw721_contract: Option<Addr>,
+    pub token_id: Option<String>,
+    pub block_time: Timestamp,
+    pub block_height: u64
+}
+
+//key is address, cw20 contract, entry id
+pub const LEDGER: Map<(&Addr, &Addr, u64), LedgerEntry> = Map::new("ledger");
+pub const LEDGER_SEQ: Item<u64> = Item::new("ledger_seq");
+
+/// A completed sale, kept after the ask and escrow entry are removed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Sale {
+    pub id: u64,
+    pub cw721_contract: Addr,
+    pub token_id: String,
+    pub seller: Addr,
+    pub buyer: Addr,
+    pub payment_asset: Addr,
+    pub price: Uint128,
+    pub protocol_fee: Uint128,
+    pub royalty: Uint128,
+    pub block_time: Timestamp,
+    pub block_height: u64
+}
+
+pub struct SaleIndexes<'a> {
+    pub token: MultiIndex<'a, (Addr, String), Sale, u64>,
+    pub collection: MultiIndex<'a, Addr, Sale, u64>,
+}
+
+impl<'a> IndexList<Sale> for SaleIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Sale>> + '_> {
+        let v: Vec<&dyn Index<Sale>> = vec![&self.token, &self.collection];
+        Box::new(v.into_iter())
+    }
+}
+
+//key is sale id
+pub fn sales<'a>() -> IndexedMap<'a, u64, Sale, SaleIndexes<'a>> {
+    let indexes = SaleIndexes {
+        token: MultiIndex::new(|s: &Sale| (s.cw721_contract.clone(), s.token_id.clone()), "sales", "sales__token"),
+        collection: MultiIndex::new(|s: &Sale| s.cw721_contract.clone(), "sales", "sales__collection"),
+    };
+    IndexedMap::new("sales", indexes)
+}
+
+pub const SALE_SEQ: Item<u64> = Item::new("sale_seq");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct CollectionStats {
+    pub listings: u64,
+    pub sales: u64,
+    /// Distinct owners with at least one NFT of the collection in escrow
+    pub escrow_holders: u64
+}
+
+pub const COLLECTION_STATS: Map<&Addr, CollectionStats> = Map::new("collection_stats");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct AssetStats {
+    pub listings: u64,
+    pub bids: u64,
+    pub sales: u64,
+    pub volume: Uint128
+}
+
+//key is cw721 contract, payment asset
+pub const COLLECTION_ASSET_STATS: Map<(&Addr, &Addr), AssetStats> = Map::new("collection_asset_stats");
+
+//key is cw721 contract, owner; value is the number of their NFTs in escrow
+pub const ESCROW_HOLDERS: Map<(&Addr, &Addr), u64> = Map::new("escrow_holders");
+
+/// Volume traded during one period (an hour or a day since the unix epoch).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VolumeBucket {
+    pub period: u64,
+    pub amount: Uint128
+}
+
+//key is cw721 contract, payment asset, period modulo the ring size
+pub const HOURLY_VOLUME: Map<(&Addr, &Addr, u64), VolumeBucket> = Map::new("hourly_volume");
+pub const DAILY_VOLUME: Map<(&Addr, &Addr, u64), VolumeBucket> = Map::new("daily_volume");
+
+/// Sale price of a collection in one payment asset from `time` on, with the
+/// running integral of the price over time up to that moment.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceObservation {
+    pub price: Uint128,
+    pub cumulative: Uint256
+}
+
+//key is cw721 contract, payment asset, block time in seconds
+pub const PRICE_OBSERVATIONS: Map<(&Addr, &Addr, u64), PriceObservation> = Map::new("price_observations");
+
+/// Trading totals of one address in one payment asset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct TraderStats {
+    pub volume_bought: Uint128,
+    pub volume_sold: Uint128,
+    pub trades: u64,
+    /// Protocol fees and royalties come out of the seller's proceeds
+    pub fees_paid: Uint128,
+    pub royalties_paid: Uint128
+}
 
-//contract, owner, token_id
-pub const CW721_DEPOSITS: Map<(&str, &str, &str), Cw721Deposits> = Map::new("cw721deposits");
+//key is trader, payment asset
+pub const TRADER_STATS: Map<(&Addr, &Addr), TraderStats> = Map::new("trader_stats");
 
+//key is payment asset, trader, day since the unix epoch modulo the ring size; amounts are the
+//volume bought and sold that day
+pub const TRADER_DAILY_VOLUME: Map<(&Addr, &Addr, u64), VolumeBucket> = Map::new("trader_volume_ring");
 
-//key can be cw721_contract, token_id
-pub const BIDS: Map<(&str, &str), Bid> = Map::new("bids");
-pub const ASKS: Map<(&str, &str), Offer> = Map::new("asks");
+//key is payment asset, volume, trader. The volume is the trader's volume over the longest
+//leaderboard window when it was last refreshed; days only ever leave the window, so it is
+//an upper bound of the volume now.
+pub const TRADER_RANKING: Map<(&Addr, u128, &Addr), Empty> = Map::new("trader_ranking");
+//key is payment asset, trader; value is the volume the trader is ranked under
+pub const TRADER_RANKED_VOLUME: Map<(&Addr, &Addr), Uint128> = Map::new("trader_ranked_volume");
@@ -0,0 +1,397 @@
+use std::convert::TryFrom;
+
+use cosmwasm_std::{Addr, Deps, Empty, Env, Order, StdError, StdResult, Storage, Uint128, Uint256};
+use cw_storage_plus::{Bound, Map};
+
+use crate::msg::{
+    AssetStatsResponse, AssetTwapResponse, CollectionStatsResponse, CollectionTwapResponse, LeaderboardResponse,
+    TraderVolume, UserAssetStats, UserStatsResponse,
+};
+use crate::state::{
+    asks, bids, Bid, Offer, Sale, TraderStats, VolumeBucket, COLLECTION_ASSET_STATS, COLLECTION_STATS, DAILY_VOLUME,
+    ESCROW_HOLDERS, HOURLY_VOLUME, PRICE_OBSERVATIONS, PriceObservation, TRADER_DAILY_VOLUME, TRADER_RANKED_VOLUME, TRADER_RANKING, TRADER_STATS,
+};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+// rolling volumes are kept in fixed rings of buckets, one slot per period
+const HOURLY_SLOTS: u64 = 24;
+const DAILY_SLOTS: u64 = 7;
+
+/// Longest window a TWAP can be asked for; older price observations are pruned.
+pub const MAX_TWAP_WINDOW: u64 = 30 * DAY;
+
+/// Longest window, in days, a leaderboard can rank over; it is also the size of each trader's
+/// ring of daily volumes.
+pub const MAX_LEADERBOARD_DAYS: u64 = 30;
+
+// ranked traders a leaderboard query looks at before it stops
+const MAX_LEADERBOARD_SCAN: usize = 100;
+
+// traders at the top of the ranking whose volume is refreshed on every trade
+const RERANKED_PER_TRADE: usize = 2;
+
+/// Expired asks a floor, stats or price query steps over before giving up. `DelistExpired`
+/// clears the rest.
+pub const MAX_EXPIRED_SKIP: usize = 100;
+
+/// Counts a new ask and its owner's escrowed NFT.
+pub fn record_listing(storage: &mut dyn Storage, ask: &Offer) -> StdResult<()> {
+    let holdings = ESCROW_HOLDERS.may_load(storage, (&ask.cw721_contract, &ask.owner))?.unwrap_or_default();
+    ESCROW_HOLDERS.save(storage, (&ask.cw721_contract, &ask.owner), &(holdings + 1))?;
+
+    let mut stats = COLLECTION_STATS.may_load(storage, &ask.cw721_contract)?.unwrap_or_default();
+    stats.listings += 1;
+    if holdings == 0 {
+        stats.escrow_holders += 1;
+    }
+    COLLECTION_STATS.save(storage, &ask.cw721_contract, &stats)?;
+
+    COLLECTION_ASSET_STATS.update(storage, (&ask.cw721_contract, &ask.cw20_contract), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.listings += 1;
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+/// Reverses `record_listing` once an ask leaves escrow, by sale or withdrawal.
+pub fn record_delisting(storage: &mut dyn Storage, ask: &Offer) -> StdResult<()> {
+    let holdings = ESCROW_HOLDERS.may_load(storage, (&ask.cw721_contract, &ask.owner))?.unwrap_or_default();
+    let mut stats = COLLECTION_STATS.may_load(storage, &ask.cw721_contract)?.unwrap_or_default();
+    stats.listings = stats.listings.saturating_sub(1);
+    if holdings <= 1 {
+        ESCROW_HOLDERS.remove(storage, (&ask.cw721_contract, &ask.owner));
+        stats.escrow_holders = stats.escrow_holders.saturating_sub(1);
+    } else {
+        ESCROW_HOLDERS.save(storage, (&ask.cw721_contract, &ask.owner), &(holdings - 1))?;
+    }
+    COLLECTION_STATS.save(storage, &ask.cw721_contract, &stats)?;
+
+    COLLECTION_ASSET_STATS.update(storage, (&ask.cw721_contract, &ask.cw20_contract), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.listings = stats.listings.saturating_sub(1);
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+pub fn record_bid_placed(storage: &mut dyn Storage, bid: &Bid) -> StdResult<()> {
+    COLLECTION_ASSET_STATS.update(storage, (&bid.cw721_contract, &bid.cw20_contract), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.bids += 1;
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+pub fn record_bid_removed(storage: &mut dyn Storage, bid: &Bid) -> StdResult<()> {
+    COLLECTION_ASSET_STATS.update(storage, (&bid.cw721_contract, &bid.cw20_contract), |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.bids = stats.bids.saturating_sub(1);
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+/// Adds a completed sale to the collection's counters and volumes.
+pub fn record_sale(storage: &mut dyn Storage, sale: &Sale) -> StdResult<()> {
+    let collection = &sale.cw721_contract;
+    let asset = &sale.payment_asset;
+
+    let mut stats = COLLECTION_STATS.may_load(storage, collection)?.unwrap_or_default();
+    stats.sales += 1;
+    COLLECTION_STATS.save(storage, collection, &stats)?;
+
+    let mut asset_stats = COLLECTION_ASSET_STATS.may_load(storage, (collection, asset))?.unwrap_or_default();
+    asset_stats.sales += 1;
+    asset_stats.volume = asset_stats.volume.checked_add(sale.price)?;
+    COLLECTION_ASSET_STATS.save(storage, (collection, asset), &asset_stats)?;
+
+    let seconds = sale.block_time.seconds();
+    add_to_ring(storage, HOURLY_VOLUME, collection, asset, seconds / HOUR, HOURLY_SLOTS, sale.price)?;
+    add_to_ring(storage, DAILY_VOLUME, collection, asset, seconds / DAY, DAILY_SLOTS, sale.price)?;
+    record_price(storage, collection, asset, seconds, sale.price)?;
+
+    record_trade(storage, &sale.buyer, sale, |stats| {
+        stats.volume_bought = stats.volume_bought.checked_add(sale.price)?;
+        Ok(())
+    })?;
+    record_trade(storage, &sale.seller, sale, |stats| {
+        stats.volume_sold = stats.volume_sold.checked_add(sale.price)?;
+        stats.fees_paid = stats.fees_paid.checked_add(sale.protocol_fee)?;
+        stats.royalties_paid = stats.royalties_paid.checked_add(sale.royalty)?;
+        Ok(())
+    })
+}
+
+fn record_trade(storage: &mut dyn Storage, trader: &Addr, sale: &Sale, side: impl FnOnce(&mut TraderStats) -> StdResult<()>) -> StdResult<()> {
+    let asset = &sale.payment_asset;
+    let mut stats = TRADER_STATS.may_load(storage, (trader, asset))?.unwrap_or_default();
+    stats.trades += 1;
+    side(&mut stats)?;
+    TRADER_STATS.save(storage, (trader, asset), &stats)?;
+
+    let day = sale.block_time.seconds() / DAY;
+    add_to_ring(storage, TRADER_DAILY_VOLUME, asset, trader, day, MAX_LEADERBOARD_DAYS, sale.price)?;
+    rerank(storage, asset, trader, day)?;
+
+    //leaders that stopped trading would otherwise keep their old volume at the top
+    let leaders = TRADER_RANKING
+        .sub_prefix(asset)
+        .keys(storage, None, None, Order::Descending)
+        .take(RERANKED_PER_TRADE)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (_, leader) in leaders {
+        rerank(storage, asset, &leader, day)?;
+    }
+    Ok(())
+}
+
+/// Moves a trader in the ranking to their volume over the last `MAX_LEADERBOARD_DAYS` days.
+/// Traders with no volume left in the window leave the ranking along with their daily volumes.
+fn rerank(storage: &mut dyn Storage, asset: &Addr, trader: &Addr, day: u64) -> StdResult<()> {
+    if let Some(ranked) = TRADER_RANKED_VOLUME.may_load(storage, (asset, trader))? {
+        TRADER_RANKING.remove(storage, (asset, ranked.u128(), trader));
+    }
+    let volume = ring_volume(storage, TRADER_DAILY_VOLUME, asset, trader, day, MAX_LEADERBOARD_DAYS)?;
+    if volume.is_zero() {
+        TRADER_RANKED_VOLUME.remove(storage, (asset, trader));
+        let stale = TRADER_DAILY_VOLUME
+            .prefix((asset, trader))
+            .keys(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for slot in stale {
+            TRADER_DAILY_VOLUME.remove(storage, (asset, trader, slot));
+        }
+        return Ok(());
+    }
+    TRADER_RANKING.save(storage, (asset, volume.u128(), trader), &Empty {})?;
+    TRADER_RANKED_VOLUME.save(storage, (asset, trader), &volume)
+}
+
+/// Starts a new price observation at `time`, closing the previous one into the running integral.
+/// Several sales in one block keep the integral and let the last price win.
+fn record_price(storage: &mut dyn Storage, collection: &Addr, asset: &Addr, time: u64, price: Uint128) -> StdResult<()> {
+    let cumulative = match last_observation(storage, collection, asset, time)? {
+        Some((since, previous)) => cumulative_at(&previous, since, time)?,
+        None => Uint256::zero()
+    };
+    PRICE_OBSERVATIONS.save(storage, (collection, asset, time), &PriceObservation { price, cumulative })?;
+
+    //only the newest observation before the longest window is still needed
+    let cutoff = time.saturating_sub(MAX_TWAP_WINDOW);
+    let stale = PRICE_OBSERVATIONS
+        .prefix((collection, asset))
+        .keys(storage, None, Some(Bound::exclusive(cutoff)), Order::Descending)
+        .skip(1)
+        .collect::<StdResult<Vec<_>>>()?;
+    for since in stale {
+        PRICE_OBSERVATIONS.remove(storage, (collection, asset, since));
+    }
+    Ok(())
+}
+
+fn last_observation(storage: &dyn Storage, collection: &Addr, asset: &Addr, time: u64) -> StdResult<Option<(u64, PriceObservation)>> {
+    PRICE_OBSERVATIONS
+        .prefix((collection, asset))
+        .range(storage, None, Some(Bound::inclusive(time)), Order::Descending)
+        .next()
+        .transpose()
+}
+
+fn cumulative_at(observation: &PriceObservation, since: u64, time: u64) -> StdResult<Uint256> {
+    let elapsed = Uint256::from(time - since);
+    let accrued = Uint256::from(observation.price).checked_mul(elapsed).map_err(StdError::overflow)?;
+    observation.cumulative.checked_add(accrued).map_err(StdError::overflow)
+}
+
+/// Time-weighted average sale price of a collection per payment asset over the last
+/// `window` seconds. When the first sale is more recent than that, the average covers
+/// the time since the first sale, which `observed` reports.
+pub fn collection_twap(deps: Deps, env: &Env, cw721_contract: Addr, window: u64) -> StdResult<CollectionTwapResponse> {
+    if window == 0 || window > MAX_TWAP_WINDOW {
+        return Err(StdError::generic_err(format!("TWAP window must be between 1 and {} seconds", MAX_TWAP_WINDOW)));
+    }
+    let now = env.block.time.seconds();
+    let start = now.saturating_sub(window);
+
+    let per_asset = COLLECTION_ASSET_STATS
+        .prefix(&cw721_contract)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut twaps = vec![];
+    for payment_asset in per_asset {
+        let (since, latest) = match last_observation(deps.storage, &cw721_contract, &payment_asset, now)? {
+            Some(observation) => observation,
+            None => continue
+        };
+        let (from, cumulative_from) = match last_observation(deps.storage, &cw721_contract, &payment_asset, start)? {
+            Some((first, observation)) => (start, cumulative_at(&observation, first, start)?),
+            None => {
+                let (first, observation) = PRICE_OBSERVATIONS
+                    .prefix((&cw721_contract, &payment_asset))
+                    .range(deps.storage, None, None, Order::Ascending)
+                    .next()
+                    .transpose()?
+                    .ok_or_else(|| StdError::not_found("PriceObservation"))?;
+                (first, observation.cumulative)
+            }
+        };
+
+        let observed = now - from;
+        let twap = if observed == 0 {
+            latest.price
+        } else {
+            let area = cumulative_at(&latest, since, now)?.checked_sub(cumulative_from).map_err(StdError::overflow)?;
+            let average = area / Uint256::from(observed);
+            Uint128::try_from(average)?
+        };
+        twaps.push(AssetTwapResponse { payment_asset, twap, observed });
+    }
+
+    Ok(CollectionTwapResponse { cw721_contract, window, twaps })
+}
+
+fn add_to_ring(storage: &mut dyn Storage, ring: Map<(&Addr, &Addr, u64), VolumeBucket>, collection: &Addr, asset: &Addr, period: u64, slots: u64, amount: Uint128) -> StdResult<()> {
+    let key = (collection, asset, period % slots);
+    let bucket = match ring.may_load(storage, key)? {
+        Some(bucket) if bucket.period == period => VolumeBucket { period, amount: bucket.amount.checked_add(amount)? },
+        _ => VolumeBucket { period, amount }
+    };
+    ring.save(storage, key, &bucket)
+}
+
+/// Sums the ring buckets that belong to the last `periods` periods, the current one included.
+fn ring_volume(storage: &dyn Storage, ring: Map<(&Addr, &Addr, u64), VolumeBucket>, collection: &Addr, asset: &Addr, period: u64, periods: u64) -> StdResult<Uint128> {
+    let mut volume = Uint128::zero();
+    for item in ring.prefix((collection, asset)).range(storage, None, None, Order::Ascending) {
+        let (_, bucket) = item?;
+        if bucket.period + periods > period {
+            volume = volume.checked_add(bucket.amount)?;
+        }
+    }
+    Ok(volume)
+}
+
+pub fn collection_stats(deps: Deps, env: &Env, cw721_contract: Addr) -> StdResult<CollectionStatsResponse> {
+    let stats = COLLECTION_STATS.may_load(deps.storage, &cw721_contract)?.unwrap_or_default();
+    let seconds = env.block.time.seconds();
+
+    let per_asset = COLLECTION_ASSET_STATS
+        .prefix(&cw721_contract)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    //expired asks stay in escrow until delisted, but no longer count as listed
+    let expired = expired_asks(deps, env, &cw721_contract, MAX_EXPIRED_SKIP)?;
+    let mut assets = vec![];
+    for (payment_asset, asset_stats) in per_asset {
+        let floor_price = floor_ask(deps, env, cw721_contract.clone(), payment_asset.clone())?.map(|ask| ask.amount);
+        let expired_listings = expired.iter().filter(|ask| ask.cw20_contract == payment_asset).count() as u64;
+        let best_offer = bids()
+            .idx
+            .asset_price
+            .sub_prefix((cw721_contract.clone(), payment_asset.clone()))
+            .range(deps.storage, None, None, Order::Descending)
+            .next()
+            .transpose()?
+            .map(|(_, bid)| bid.amount);
+        assets.push(AssetStatsResponse {
+            volume_24h: ring_volume(deps.storage, HOURLY_VOLUME, &cw721_contract, &payment_asset, seconds / HOUR, HOURLY_SLOTS)?,
+            volume_7d: ring_volume(deps.storage, DAILY_VOLUME, &cw721_contract, &payment_asset, seconds / DAY, DAILY_SLOTS)?,
+            payment_asset,
+            listings: asset_stats.listings.saturating_sub(expired_listings),
+            floor_price,
+            best_offer,
+            sales: asset_stats.sales,
+            volume: asset_stats.volume
+        });
+    }
+
+    Ok(CollectionStatsResponse {
+        cw721_contract,
+        listings: stats.listings.saturating_sub(expired.len() as u64),
+        sales: stats.sales,
+        escrow_holders: stats.escrow_holders,
+        assets
+    })
+}
+
+/// Cheapest ask of a collection in a payment asset that has not expired. None when there is
+/// no such ask among the `MAX_EXPIRED_SKIP` cheapest expired ones and the one after them.
+pub fn floor_ask(deps: Deps, env: &Env, cw721_contract: Addr, payment_asset: Addr) -> StdResult<Option<Offer>> {
+    asks()
+        .idx
+        .asset_price
+        .sub_prefix((cw721_contract, payment_asset))
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(MAX_EXPIRED_SKIP + 1)
+        .map(|item| item.map(|(_, ask)| ask))
+        .find(|item| item.as_ref().map(|ask| !ask.is_expired(env.block.time)).unwrap_or(true))
+        .transpose()
+}
+
+/// Up to `limit` expired asks of a collection, soonest expiry first.
+pub fn expired_asks(deps: Deps, env: &Env, cw721_contract: &Addr, limit: usize) -> StdResult<Vec<Offer>> {
+    asks()
+        .idx
+        .expiry
+        .sub_prefix(cw721_contract.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, ask)| ask))
+        .take_while(|item| item.as_ref().map(|ask| ask.is_expired(env.block.time)).unwrap_or(true))
+        .take(limit)
+        .collect()
+}
+
+pub fn user_stats(deps: Deps, address: Addr) -> StdResult<UserStatsResponse> {
+    let assets = TRADER_STATS
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(payment_asset, stats)| UserAssetStats {
+            payment_asset,
+            volume_bought: stats.volume_bought,
+            volume_sold: stats.volume_sold,
+            trades: stats.trades,
+            fees_paid: stats.fees_paid,
+            royalties_paid: stats.royalties_paid
+        }))
+        .collect::<StdResult<_>>()?;
+    Ok(UserStatsResponse { address, assets })
+}
+
+/// Ranks traders by the volume they bought and sold in a payment asset during the
+/// current day and the `days - 1` before it.
+///
+/// Traders are visited from the highest ranked volume down. The ranked volume never falls
+/// below the volume in the window, so the scan stops once it drops below the last place.
+/// At most `MAX_LEADERBOARD_SCAN` traders are looked at.
+pub fn leaderboard(deps: Deps, env: &Env, payment_asset: Addr, days: u64, limit: usize) -> StdResult<LeaderboardResponse> {
+    if days == 0 || days > MAX_LEADERBOARD_DAYS {
+        return Err(StdError::generic_err(format!("Leaderboard window must be between 1 and {} days", MAX_LEADERBOARD_DAYS)));
+    }
+    let today = env.block.time.seconds() / DAY;
+
+    let mut traders: Vec<TraderVolume> = vec![];
+    let ranked = TRADER_RANKING
+        .sub_prefix(&payment_asset)
+        .keys(deps.storage, None, None, Order::Descending)
+        .take(MAX_LEADERBOARD_SCAN);
+    for item in ranked {
+        let (ranked_volume, address) = item?;
+        if traders.len() >= limit && traders.last().map(|last| Uint128::from(ranked_volume) < last.volume).unwrap_or(true) {
+            break;
+        }
+        let volume = ring_volume(deps.storage, TRADER_DAILY_VOLUME, &payment_asset, &address, today, days)?;
+        if volume.is_zero() {
+            continue;
+        }
+        traders.push(TraderVolume { address, volume });
+        //highest volume first, ties broken by address so the order is stable
+        traders.sort_by(|a, b| b.volume.cmp(&a.volume).then_with(|| a.address.cmp(&b.address)));
+        traders.truncate(limit);
+    }
+
+    Ok(LeaderboardResponse { payment_asset, days, traders })
+}